@@ -1,5 +1,19 @@
 use crate::registers::Registers;
 use memory::Memory;
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
+use std::fmt;
+
+type Hook = dyn FnMut(u8, &Registers);
+
+/// Called with each opcode once it is fetched, before it is executed
+pub struct DecodeHook(Box<Hook>);
+
+impl fmt::Debug for DecodeHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DecodeHook")
+    }
+}
 
 #[derive(Default, Debug)]
 pub struct Cpu {
@@ -7,6 +21,7 @@ pub struct Cpu {
     pub registers: Registers,
     pub(crate) halt: bool,
     pub(crate) stop: bool,
+    decode_hook: Option<DecodeHook>,
 }
 
 impl Cpu {
@@ -20,6 +35,7 @@ impl Cpu {
             registers,
             halt: false,
             stop: false,
+            decode_hook: None,
         }
     }
 
@@ -31,7 +47,57 @@ impl Cpu {
         self.memory.borrow().master_enabled()
     }
 
+    /// Test ROMs signal their result with special opcodes, such as `LD B,B` for
+    /// the mooneye suite, the hook lets the host see them
+    pub fn set_decode_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(u8, &Registers) + 'static,
+    {
+        self.decode_hook = Some(DecodeHook(Box::new(hook)));
+    }
+
+    pub(crate) fn decoded(&mut self, opcode: u8) {
+        if let Some(DecodeHook(hook)) = self.decode_hook.as_mut() {
+            hook(opcode, &self.registers)
+        }
+    }
+
     pub fn print_debug(&mut self) {
         if self.memory.borrow_mut().get_debug().is_some() {}
     }
 }
+
+impl Snapshot for Cpu {
+    fn snapshot(&self, writer: &mut Writer) {
+        let registers = &self.registers;
+        writer.u8(registers.a);
+        writer.u8(registers.f.get_all());
+        writer.u8(registers.b);
+        writer.u8(registers.c);
+        writer.u8(registers.d);
+        writer.u8(registers.e);
+        writer.u8(registers.h);
+        writer.u8(registers.l);
+        writer.u16(registers.sp);
+        writer.u16(registers.pc);
+        writer.bool(self.halt);
+        writer.bool(self.stop);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        let registers = &mut self.registers;
+        registers.a = reader.u8()?;
+        registers.f.set_all(reader.u8()?);
+        registers.b = reader.u8()?;
+        registers.c = reader.u8()?;
+        registers.d = reader.u8()?;
+        registers.e = reader.u8()?;
+        registers.h = reader.u8()?;
+        registers.l = reader.u8()?;
+        registers.sp = reader.u16()?;
+        registers.pc = reader.u16()?;
+        self.halt = reader.bool()?;
+        self.stop = reader.bool()?;
+        Ok(())
+    }
+}
@@ -90,6 +90,7 @@ pub async fn run(cpu: Cpu) -> Result<Finished, Error> {
 
     if !cpu.borrow().halt && !cpu.borrow().stop {
         let (opcode, cycles) = Get::Next.get(cpu.clone()).await?;
+        cpu.borrow_mut().decoded(opcode);
         // println!("New Cpu Execution, Opcode: {:#X}", opcode);
 
         let execute = decode(cpu.clone(), opcode).await?;
@@ -1,29 +1,88 @@
+use clap::Parser;
+use headless::{parse_address, Headless};
+use soc::{Options, TryInit, SOC};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use windows::Windows;
 
-// ressources/test_roms/cpu_instrs/individual/01-special.gb (PASSED)
-// ressources/test_roms/cpu_instrs/individual/02-interrupts.gb (PASSED)
-// ressources/test_roms/cpu_instrs/individual/03-op sp,hl.gb (PASSED)
-// ressources/test_roms/cpu_instrs/individual/04-op r,imm.gb (PASSED)
-// ressources/test_roms/cpu_instrs/individual/05-op rp.gb (PASSED)
-// ressources/test_roms/cpu_instrs/individual/06-ld r,r.gb (PASSED)
-// ressources/test_roms/cpu_instrs/individual/07-jr,jp,call,ret,rst.gb (PASSED)
-// ressources/test_roms/cpu_instrs/individual/08-misc instrs.gb(PASSED)
-// ressources/test_roms/cpu_instrs/individual/09-op r,r.gb (PASSED)
-// ressources/test_roms/cpu_instrs/individual/10-bit ops.gb (PASSED)
-// ressources/test_roms/cpu_instrs/individual/11-op a,(hl).gb (PASSED)
+/// Boot ROM used when none is given and it is present
+const DEFAULT_BIOS: &str = "ressources/bios/dmg_boot.bin";
 
-pub fn main() {
-    // Windows::run("ressources/test_roms/cpu_instrs/cpu_instrs.gb");
-    // Windows::run("ressources/test_roms/cpu_instrs/individual/01-special.gb");
-    // Windows::run("ressources/test_roms/cpu_instrs/individual/02-interrupts.gb");
-    //Windows::run("ressources/test_roms/cpu_instrs/individual/03-op sp,hl.gb");
-    // Windows::run("ressources/test_roms/cpu_instrs/individual/04-op r,imm.gb");
-    // Windows::run("ressources/test_roms/cpu_instrs/individual/05-op rp.gb");
-    // Windows::run("ressources/test_roms/cpu_instrs/individual/06-ld r,r.gb");
-    // Windows::run("ressources/test_roms/cpu_instrs/individual/07-jr,jp,call,ret,rst.gb");
-    // Windows::run("ressources/test_roms/cpu_instrs/individual/08-misc instrs.gb");
-    // Windows::run("ressources/test_roms/cpu_instrs/individual/09-op r,r.gb");
-    // Windows::run("ressources/test_roms/cpu_instrs/individual/10-bit ops.gb");
-    // Windows::run("ressources/test_roms/cpu_instrs/individual/11-op a,(hl).gb");
-    Windows::run("roms/Tetris.gb");
+/// A Game Boy emulator
+#[derive(Parser, Debug)]
+#[command(name = "gbmu")]
+struct Args {
+    /// ROM to run
+    rom: String,
+    /// Boot ROM run before the cartridge [default: ressources/bios/dmg_boot.bin when present]
+    #[arg(long, conflicts_with = "no_bios")]
+    bios: Option<PathBuf>,
+    /// Start right on the cartridge, with the registers the boot ROM leaves behind
+    #[arg(long)]
+    no_bios: bool,
+    /// Directory of the battery saves and save states [default: next to the ROM]
+    #[arg(long)]
+    save_dir: Option<PathBuf>,
+    /// Run without any window
+    #[arg(long)]
+    headless: bool,
+    /// Frames to run without any window
+    #[arg(long, default_value_t = 600, requires = "headless")]
+    frames: u64,
+    /// Address the debugger breaks on, in hexadecimal, can be repeated
+    #[arg(long = "break", value_name = "ADDR", value_parser = parse_address)]
+    breakpoints: Vec<u16>,
+    /// Size of the emulator window, in multiples of the 160x144 screen
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..=8))]
+    scale: u32,
+    /// Frames between two rewind snapshots
+    #[arg(long, value_name = "FRAMES", default_value_t = soc::rewind::DEFAULT_INTERVAL)]
+    #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+    rewind_interval: u64,
+    /// Memory kept for the rewind snapshots, in MiB
+    #[arg(long, value_name = "MIB", default_value_t = soc::rewind::DEFAULT_BUDGET >> 20)]
+    rewind_budget: usize,
+}
+
+impl Args {
+    fn options(&self) -> Options {
+        let bios = match (&self.bios, self.no_bios) {
+            (_, true) => None,
+            (Some(bios), false) => Some(bios.clone()),
+            (None, false) => Some(PathBuf::from(DEFAULT_BIOS)).filter(|bios| bios.exists()),
+        };
+        Options {
+            bios,
+            saves: memory::SaveManager::new(self.save_dir.clone(), memory::SaveKey::Path),
+            breakpoints: self.breakpoints.clone(),
+        }
+    }
+}
+
+pub fn main() -> ExitCode {
+    let args = Args::parse();
+    if let Some(directory) = &args.save_dir {
+        if let Err(e) = std::fs::create_dir_all(directory) {
+            eprintln!("Could not create {}: {}", directory.display(), e);
+            return ExitCode::FAILURE;
+        }
+    }
+    let soc = match SOC::try_init_with(&args.rom, &args.options()) {
+        Ok(soc) => soc,
+        Err(e) => {
+            eprintln!("Could not load {}: {}", Path::new(&args.rom).display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    soc.borrow_mut()
+        .set_rewind(args.rewind_interval, args.rewind_budget << 20);
+    if args.headless {
+        if let Err(e) = Headless::new(soc).run(args.frames) {
+            eprintln!("Stopped: {}", e);
+            return ExitCode::FAILURE;
+        }
+    } else {
+        Windows::run(soc, args.scale);
+    }
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,188 @@
+pub mod screenshot;
+
+use ppu::ppu::{FRAME_HEIGHT, FRAME_WIDTH};
+use soc::mode::Mode;
+use soc::{Event, SOC};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// Clock ticks between two frames while the LCD is on
+pub const CYCLES_PER_FRAME: u64 = 70_224;
+
+/// Directory of the test ROMs, `ressources/test_roms` when it is not set
+pub const TEST_ROMS: &str = "GBMU_TEST_ROMS";
+
+/// `path` in the test ROMs directory, see `TEST_ROMS`. The ROMs are not part
+/// of the repository so the suites using them are ignored by default, run them
+/// with `make test.roms`. A suite run without its ROMs fails instead of
+/// passing without testing anything.
+pub fn test_roms(path: &str) -> PathBuf {
+    let directory = match std::env::var_os(TEST_ROMS) {
+        Some(directory) => PathBuf::from(directory),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("../../ressources/test_roms"),
+    };
+    let path = directory.join(path);
+    assert!(
+        path.exists(),
+        "{} not found, set {} to the test ROMs directory",
+        path.display(),
+        TEST_ROMS
+    );
+    path
+}
+
+/// A 32 KiB ROM jumping from the entry point to `code` at 0x150, with its
+/// global checksum, written to the temporary directory as `name`. For the
+/// tests that need a machine running some code of their own.
+pub fn build_rom(name: &str, code: &[u8]) -> PathBuf {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x150..0x150 + code.len()].copy_from_slice(code);
+    let checksum = rom
+        .iter()
+        .fold(0u16, |sum, byte| sum.wrapping_add(*byte as u16));
+    rom[0x14E..0x150].copy_from_slice(&checksum.to_be_bytes());
+    let path = std::env::temp_dir().join(name);
+    fs::write(&path, rom).expect("Could not write the ROM");
+    path
+}
+
+/// An address given in hexadecimal on the command line, with or without a
+/// `0x` or `$` prefix
+pub fn parse_address(address: &str) -> Result<u16, String> {
+    let digits = address
+        .strip_prefix("0x")
+        .or_else(|| address.strip_prefix("0X"))
+        .or_else(|| address.strip_prefix('$'))
+        .unwrap_or(address);
+    u16::from_str_radix(digits, 16).map_err(|e| format!("invalid address {}: {}", address, e))
+}
+
+/// Why `Headless::run_until` stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The condition was met
+    Met,
+    /// The frames ran out first
+    Timeout,
+    /// The CPU or the PPU failed
+    Error(String),
+}
+
+/// Frontend over `SOC::run` without any window, for the CI and the
+/// regression tests. The machine runs one instruction at a time as fast as
+/// possible, so conditions are checked between every instruction.
+pub struct Headless {
+    soc: SOC,
+}
+
+impl Headless {
+    pub fn new(soc: SOC) -> Self {
+        Self { soc }
+    }
+
+    pub fn soc(&self) -> &SOC {
+        &self.soc
+    }
+
+    /// Run `frames` frames
+    pub fn run(&mut self, frames: u64) -> Result<(), String> {
+        match self.run_until(frames, |_| false) {
+            Outcome::Error(error) => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Run until `condition` holds, for at most `frames` frames. A frame lasts
+    /// `CYCLES_PER_FRAME` ticks when the LCD is off and no frame is drawn.
+    pub fn run_until<F>(&mut self, frames: u64, mut condition: F) -> Outcome
+    where
+        F: FnMut(&SOC) -> bool,
+    {
+        let status = self.soc.borrow().get_status();
+        let (last_frame, last_cycle) = {
+            let soc = self.soc.borrow();
+            (
+                soc.frames() + frames,
+                soc.cycles() + (frames + 1) * CYCLES_PER_FRAME,
+            )
+        };
+        loop {
+            if condition(&self.soc) {
+                return Outcome::Met;
+            }
+            let mut soc = self.soc.borrow_mut();
+            if soc.frames() >= last_frame || soc.cycles() >= last_cycle {
+                return Outcome::Timeout;
+            }
+            status.borrow_mut().mode(Mode::Instruction);
+            soc.run();
+            for event in soc.take_events() {
+                if let Event::Error(error) = event {
+                    return Outcome::Error(error);
+                }
+            }
+        }
+    }
+
+    /// The screen as shades from 0 (white) to 3 (black), see `screenshot::compare`
+    pub fn shades(&self) -> Vec<u8> {
+        self.soc.borrow().get_ppu().borrow().shades()
+    }
+
+    /// The screen as RGBA
+    pub fn screenshot(&self) -> Vec<u8> {
+        self.soc.borrow().get_ppu().borrow().rgba()
+    }
+
+    pub fn save_png(&self, path: &Path) -> io::Result<()> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        let file = io::BufWriter::new(fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, FRAME_WIDTH as u32, FRAME_HEIGHT as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(invalid)?;
+        writer.write_image_data(&self.screenshot()).map_err(invalid)
+    }
+}
+
+#[cfg(test)]
+mod test_headless {
+    use super::{build_rom, parse_address, Headless, Outcome};
+    use soc::TryInit;
+
+    /// A ROM counting in A and writing it all over WRAM
+    fn headless(name: &str) -> Headless {
+        let rom = build_rom(name, &[0x21, 0x00, 0xC0, 0x3C, 0x77, 0x23, 0x18, 0xFB]);
+        Headless::new(soc::SOC::try_init(rom.to_str().unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_run_frames() {
+        let mut headless = headless("gbmu_test_headless_frames.gb");
+
+        headless.run(3).unwrap();
+        assert_eq!(headless.soc().borrow().frames(), 3);
+        assert_eq!(headless.screenshot().len(), 160 * 144 * 4);
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut headless = headless("gbmu_test_headless_until.gb");
+        let pc = |soc: &soc::SOC| soc.borrow().get_cpu().borrow().registers.pc;
+
+        assert_eq!(headless.run_until(1, |soc| pc(soc) == 0x0155), Outcome::Met);
+        assert_eq!(
+            headless.run_until(1, |soc| pc(soc) == 0x0000),
+            Outcome::Timeout
+        );
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("0x0150"), Ok(0x0150));
+        assert_eq!(parse_address("$FF80"), Ok(0xFF80));
+        assert_eq!(parse_address("c000"), Ok(0xC000));
+        assert!(parse_address("0x10000").is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use clap::Parser;
+use headless::{parse_address, Headless, Outcome};
+use soc::{Options, TryInit, SOC};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Exit status when the condition was not met in time
+const TIMEOUT: u8 = 1;
+/// Exit status when the ROM could not be loaded or the machine failed
+const FAILURE: u8 = 2;
+
+/// Run a ROM without any window, for the CI and the regression tests
+#[derive(Parser, Debug)]
+#[command(name = "gbmu-headless")]
+struct Args {
+    /// ROM to run
+    rom: String,
+    /// Boot ROM run before the cartridge
+    #[arg(long)]
+    bios: Option<PathBuf>,
+    /// Directory of the battery saves [default: next to the ROM]
+    #[arg(long)]
+    save_dir: Option<PathBuf>,
+    /// Frames to run at most
+    #[arg(long, default_value_t = 600)]
+    frames: u64,
+    /// Stop once the CPU reaches this address, in hexadecimal
+    #[arg(long, value_name = "ADDR", value_parser = parse_address)]
+    until_pc: Option<u16>,
+    /// Write the last frame there as a PNG
+    #[arg(long)]
+    screenshot: Option<PathBuf>,
+}
+
+/// Exits with 0 once the frames ran or the condition was met, see `TIMEOUT` and `FAILURE`
+pub fn main() -> ExitCode {
+    let args = Args::parse();
+    let options = Options {
+        bios: args.bios.clone(),
+        saves: memory::SaveManager::new(args.save_dir.clone(), memory::SaveKey::Path),
+        breakpoints: Vec::new(),
+    };
+    let soc = match SOC::try_init_with(&args.rom, &options) {
+        Ok(soc) => soc,
+        Err(e) => {
+            eprintln!("Could not load {}: {}", args.rom, e);
+            return ExitCode::from(FAILURE);
+        }
+    };
+    let mut headless = Headless::new(soc);
+    let outcome = match args.until_pc {
+        Some(pc) => headless.run_until(args.frames, |soc| {
+            soc.borrow().get_cpu().borrow().registers.pc == pc
+        }),
+        None => match headless.run(args.frames) {
+            Ok(()) => Outcome::Met,
+            Err(error) => Outcome::Error(error),
+        },
+    };
+    if let Some(path) = &args.screenshot {
+        if let Err(e) = headless.save_png(path) {
+            eprintln!("Could not write {}: {}", path.display(), e);
+            return ExitCode::from(FAILURE);
+        }
+    }
+    let frames = headless.soc().borrow().frames();
+    match outcome {
+        Outcome::Met => ExitCode::SUCCESS,
+        Outcome::Timeout => {
+            eprintln!("Condition not met after {} frames", frames);
+            ExitCode::from(TIMEOUT)
+        }
+        Outcome::Error(error) => {
+            eprintln!("Stopped after {} frames: {}", frames, error);
+            ExitCode::from(FAILURE)
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Compare the screen with reference images, one shade from 0 (white) to
+//! 3 (black) per pixel as `ppu::Ppu::shades` gives them.
+
+use ppu::ppu::{FRAME_HEIGHT, FRAME_WIDTH};
+use std::path::Path;
+use std::{fs, io};
+
+/// Pixels matching the reference are dimmed in the diff image, the others are red
+const MISMATCH: [u8; 3] = [0xFF, 0x00, 0x00];
+
+fn invalid<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn gray(shade: u8) -> u8 {
+    0xFF - shade.min(3) * 0x55
+}
+
+/// Read a 160x144 reference PNG, each pixel is turned to the closest shade
+pub fn load_png(path: &Path) -> io::Result<Vec<u8>> {
+    let mut decoder = png::Decoder::new(fs::File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(invalid)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(invalid)?;
+    if (info.width as usize, info.height as usize) != (FRAME_WIDTH, FRAME_HEIGHT) {
+        return Err(invalid(format!(
+            "{} is {}x{}, not {}x{}",
+            path.display(),
+            info.width,
+            info.height,
+            FRAME_WIDTH,
+            FRAME_HEIGHT
+        )));
+    }
+    let channels = info.color_type.samples();
+    Ok(buffer[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|pixel| {
+            let luma = match channels {
+                1 | 2 => pixel[0] as u32,
+                _ => (pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000,
+            };
+            ((0xFF - luma + 0x2A) / 0x55) as u8
+        })
+        .collect())
+}
+
+/// Write the shades as a grayscale PNG
+pub fn save_png(path: &Path, shades: &[u8]) -> io::Result<()> {
+    let pixels: Vec<u8> = shades.iter().copied().map(gray).collect();
+    let file = io::BufWriter::new(fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, FRAME_WIDTH as u32, FRAME_HEIGHT as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(invalid)?;
+    writer.write_image_data(&pixels).map_err(invalid)
+}
+
+/// Write an RGB PNG of `actual` where the pixels differing from `expected` are red
+pub fn save_diff(path: &Path, actual: &[u8], expected: &[u8]) -> io::Result<()> {
+    let pixels: Vec<u8> = actual
+        .iter()
+        .zip(expected)
+        .flat_map(|(actual, expected)| match actual == expected {
+            true => [0x80 + gray(*actual) / 2; 3],
+            false => MISMATCH,
+        })
+        .collect();
+    let file = io::BufWriter::new(fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, FRAME_WIDTH as u32, FRAME_HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(invalid)?;
+    writer.write_image_data(&pixels).map_err(invalid)
+}
+
+/// Count the pixels of `actual` differing from the `reference` PNG, when
+/// there are some the diff image is written to `diff`
+pub fn compare(actual: &[u8], reference: &Path, diff: &Path) -> io::Result<usize> {
+    let expected = load_png(reference)?;
+    let mismatches = actual
+        .iter()
+        .zip(&expected)
+        .filter(|(actual, expected)| actual != expected)
+        .count();
+    if mismatches > 0 {
+        save_diff(diff, actual, &expected)?;
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod test_screenshot {
+    use super::{compare, load_png, save_png};
+    use ppu::ppu::{FRAME_HEIGHT, FRAME_WIDTH};
+
+    fn shades() -> Vec<u8> {
+        (0..FRAME_WIDTH * FRAME_HEIGHT)
+            .map(|pixel| (pixel % 4) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_save_load_png() {
+        let path = std::env::temp_dir().join("gbmu_test_screenshot.png");
+        save_png(&path, &shades()).unwrap();
+
+        assert_eq!(load_png(&path).unwrap(), shades());
+    }
+
+    #[test]
+    fn test_compare() {
+        let reference = std::env::temp_dir().join("gbmu_test_screenshot_reference.png");
+        let diff = std::env::temp_dir().join("gbmu_test_screenshot_diff.png");
+        let _ = std::fs::remove_file(&diff);
+        save_png(&reference, &shades()).unwrap();
+
+        assert_eq!(compare(&shades(), &reference, &diff).unwrap(), 0);
+        assert!(!diff.exists());
+        let mut actual = shades();
+        actual[0] = 3;
+        actual[1] = 3;
+        assert_eq!(compare(&actual, &reference, &diff).unwrap(), 2);
+        assert!(diff.exists());
+    }
+}
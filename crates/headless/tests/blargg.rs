@@ -0,0 +1,160 @@
+//! Blargg's test ROMs report on the serial port, each ROM of a suite is run
+//! until it prints "Passed" or "Failed", see `headless::test_roms`.
+
+use headless::{build_rom, test_roms, Headless, Outcome, CYCLES_PER_FRAME};
+use soc::{TryInit, SOC};
+
+const CYCLES_PER_SECOND: u64 = 4_194_304;
+
+struct Suite {
+    name: &'static str,
+    /// Paths relative to the test ROMs directory
+    roms: &'static [&'static str],
+    /// Emulated seconds a ROM gets to report
+    budget: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Report {
+    Passed,
+    Failed(String),
+    Timeout(String),
+    Error(String),
+}
+
+fn run(rom: &str, budget: u64) -> Report {
+    let soc = match SOC::try_init(rom) {
+        Ok(soc) => soc,
+        Err(e) => return Report::Error(e.to_string()),
+    };
+    soc.borrow().capture_serial();
+    let mut headless = Headless::new(soc);
+    let mut output = String::new();
+    let frames = budget * CYCLES_PER_SECOND / CYCLES_PER_FRAME;
+    let outcome = headless.run_until(frames, |soc| {
+        output.push_str(&String::from_utf8_lossy(&soc.borrow().take_serial()));
+        output.contains("Passed") || output.contains("Failed")
+    });
+    match outcome {
+        Outcome::Met if output.contains("Passed") => Report::Passed,
+        Outcome::Met => Report::Failed(output),
+        Outcome::Timeout => Report::Timeout(output),
+        Outcome::Error(error) => Report::Error(error),
+    }
+}
+
+fn run_suite(suite: &Suite) {
+    let reports: Vec<(&str, Report)> = suite
+        .roms
+        .iter()
+        .map(|rom| (*rom, run(test_roms(rom).to_str().unwrap(), suite.budget)))
+        .collect();
+    for (rom, report) in &reports {
+        match report {
+            Report::Passed => println!("{}: passed", rom),
+            Report::Failed(output) => println!("{}: failed\n{}", rom, output),
+            Report::Timeout(output) => println!("{}: timeout\n{}", rom, output),
+            Report::Error(error) => println!("{}: error {}", rom, error),
+        }
+    }
+    let failed = reports
+        .iter()
+        .filter(|(_, report)| *report != Report::Passed)
+        .count();
+    assert_eq!(
+        failed,
+        0,
+        "{}: {} of {} failed",
+        suite.name,
+        failed,
+        reports.len()
+    );
+}
+
+#[test]
+#[ignore = "needs the test ROMs"]
+fn test_cpu_instrs() {
+    run_suite(&Suite {
+        name: "cpu_instrs",
+        roms: &[
+            "cpu_instrs/individual/01-special.gb",
+            "cpu_instrs/individual/02-interrupts.gb",
+            "cpu_instrs/individual/03-op sp,hl.gb",
+            "cpu_instrs/individual/04-op r,imm.gb",
+            "cpu_instrs/individual/05-op rp.gb",
+            "cpu_instrs/individual/06-ld r,r.gb",
+            "cpu_instrs/individual/07-jr,jp,call,ret,rst.gb",
+            "cpu_instrs/individual/08-misc instrs.gb",
+            "cpu_instrs/individual/09-op r,r.gb",
+            "cpu_instrs/individual/10-bit ops.gb",
+            "cpu_instrs/individual/11-op a,(hl).gb",
+        ],
+        budget: 30,
+    });
+}
+
+#[test]
+#[ignore = "needs the test ROMs"]
+fn test_instr_timing() {
+    run_suite(&Suite {
+        name: "instr_timing",
+        roms: &["instr_timing/instr_timing.gb"],
+        budget: 10,
+    });
+}
+
+#[test]
+#[ignore = "needs the test ROMs"]
+fn test_mem_timing() {
+    run_suite(&Suite {
+        name: "mem_timing",
+        roms: &[
+            "mem_timing/individual/01-read_timing.gb",
+            "mem_timing/individual/02-write_timing.gb",
+            "mem_timing/individual/03-modify_timing.gb",
+        ],
+        budget: 10,
+    });
+}
+
+#[test]
+#[ignore = "needs the test ROMs"]
+fn test_halt_bug() {
+    run_suite(&Suite {
+        name: "halt_bug",
+        roms: &["halt_bug.gb"],
+        budget: 10,
+    });
+}
+
+/// A ROM printing `message` on the serial port then looping
+fn serial_rom(name: &str, message: &str) -> String {
+    let mut code = Vec::new();
+    for byte in message.bytes() {
+        // LD A,byte; LDH (SB),A; LD A,0x81; LDH (SC),A
+        code.extend([0x3E, byte, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02]);
+    }
+    code.extend([0x18, 0xFE]);
+    build_rom(name, &code).to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_harness_passed() {
+    let rom = serial_rom("gbmu_test_blargg_passed.gb", "Test\nPassed");
+
+    assert_eq!(run(&rom, 1), Report::Passed);
+}
+
+#[test]
+fn test_harness_failed() {
+    let rom = serial_rom("gbmu_test_blargg_failed.gb", "Test\nFailed");
+
+    assert_eq!(run(&rom, 1), Report::Failed("Test\nFailed".to_string()));
+}
+
+#[test]
+fn test_harness_timeout() {
+    let rom = serial_rom("gbmu_test_blargg_timeout.gb", "Test");
+
+    assert_eq!(run(&rom, 1), Report::Timeout("Test".to_string()));
+}
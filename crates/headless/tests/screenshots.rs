@@ -0,0 +1,138 @@
+//! The screen is compared with reference images once the ROM executes
+//! `LD B,B`, which dmg-acid2 and the Mealybug tearoom tests do when the frame
+//! is ready, see `headless::test_roms`. The diff images are written to
+//! `target/tmp/screenshots`.
+
+use headless::{build_rom, screenshot, test_roms, Headless, Outcome};
+use soc::{TryInit, SOC};
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// `LD B,B`, the software breakpoint the tests execute once done
+const LD_B_B: u8 = 0x40;
+const FRAMES: u64 = 120;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Report {
+    Matched,
+    Mismatched(usize),
+    Timeout,
+    Error(String),
+}
+
+fn run(rom: &Path, reference: &Path) -> Report {
+    let soc = match SOC::try_init(rom.to_str().unwrap()) {
+        Ok(soc) => soc,
+        Err(e) => return Report::Error(e.to_string()),
+    };
+    let done = Rc::new(Cell::new(false));
+    let hook = done.clone();
+    soc.borrow()
+        .set_decode_hook(move |opcode, _| hook.set(hook.get() || opcode == LD_B_B));
+    let mut headless = Headless::new(soc);
+    match headless.run_until(FRAMES, |_| done.get()) {
+        Outcome::Met => {}
+        Outcome::Timeout => return Report::Timeout,
+        Outcome::Error(error) => return Report::Error(error),
+    }
+    let diffs = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("screenshots");
+    let _ = std::fs::create_dir_all(&diffs);
+    let diff = diffs.join(rom.with_extension("png").file_name().unwrap());
+    match screenshot::compare(&headless.shades(), reference, &diff) {
+        Ok(0) => Report::Matched,
+        Ok(mismatches) => Report::Mismatched(mismatches),
+        Err(e) => Report::Error(e.to_string()),
+    }
+}
+
+fn run_all(name: &str, tests: Vec<(PathBuf, PathBuf)>) {
+    assert!(!tests.is_empty(), "{}: no ROM found", name);
+    let reports: Vec<_> = tests
+        .iter()
+        .map(|(rom, reference)| (rom.file_name().unwrap(), run(rom, reference)))
+        .collect();
+    for (rom, report) in &reports {
+        println!("{}: {:?}", rom.to_string_lossy(), report);
+    }
+    let failed = reports
+        .iter()
+        .filter(|(_, report)| *report != Report::Matched)
+        .count();
+    assert_eq!(
+        failed,
+        0,
+        "{}: {} of {} failed",
+        name,
+        failed,
+        reports.len()
+    );
+}
+
+#[test]
+#[ignore = "needs the test ROMs"]
+fn test_dmg_acid2() {
+    let directory = test_roms("dmg-acid2");
+    run_all(
+        "dmg-acid2",
+        vec![(
+            directory.join("dmg-acid2.gb"),
+            directory.join("reference-dmg.png"),
+        )],
+    );
+}
+
+/// `mealybug/<test>.gb` is compared with `mealybug/expected/DMG-blob/<test>.png`
+#[test]
+#[ignore = "needs the test ROMs"]
+fn test_mealybug_tearoom() {
+    let directory = test_roms("mealybug");
+    let tests = std::fs::read_dir(&directory)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|rom| rom.extension().is_some_and(|ext| ext == "gb"))
+        .map(|rom| {
+            let name = rom.with_extension("png");
+            let reference = directory
+                .join("expected/DMG-blob")
+                .join(name.file_name().unwrap());
+            (rom, reference)
+        })
+        .collect();
+    run_all("mealybug", tests);
+}
+
+/// A ROM drawing a white frame, BGP maps every color to white, then
+/// executing `LD B,B`
+fn done_rom(name: &str) -> PathBuf {
+    build_rom(
+        name,
+        &[
+            0x3E, 0x00, // LD A,0x00
+            0xE0, 0x47, // LDH (BGP),A
+            0x3E, 0x91, // LD A,0x91
+            0xE0, 0x40, // LDH (LCDC),A
+            0xF0, 0x44, // LDH A,(LY)
+            0xFE, 0x00, // CP 0x00
+            0x20, 0xFA, // JR NZ,-6
+            0xF0, 0x44, // LDH A,(LY)
+            0xFE, 0x90, // CP 0x90
+            0x20, 0xFA, // JR NZ,-6
+            LD_B_B, 0x18, 0xFE,
+        ],
+    )
+}
+
+#[test]
+fn test_harness() {
+    let rom = done_rom("gbmu_test_screenshots_harness.gb");
+    let white = std::env::temp_dir().join("gbmu_test_screenshots_white.png");
+    let black = std::env::temp_dir().join("gbmu_test_screenshots_black.png");
+    screenshot::save_png(&white, &[0; 160 * 144]).unwrap();
+    screenshot::save_png(&black, &[3; 160 * 144]).unwrap();
+
+    assert_eq!(run(&rom, &white), Report::Matched);
+    assert_eq!(run(&rom, &black), Report::Mismatched(160 * 144));
+}
@@ -2,8 +2,7 @@ use crate::MemoryBus;
 use shared::Error;
 use std::convert::AsRef;
 use std::fs;
-use std::path::PathBuf;
-use std::str;
+use std::path::Path;
 
 #[derive(Debug)]
 pub struct Bios {
@@ -39,17 +38,17 @@ impl MemoryBus for Bios {
     }
 }
 
+impl From<Vec<u8>> for Bios {
+    fn from(data: Vec<u8>) -> Self {
+        Bios { data }
+    }
+}
+
 impl Bios {
+    /// The boot ROM fetched in `ressources` by `make`, found from the crate
+    /// directory whatever the working directory is
     pub fn new() -> Self {
-        let output = std::process::Command::new("git")
-            .args(&["rev-parse", "--show-toplevel"])
-            .output()
-            .unwrap();
-        let git_root = str::from_utf8(&output.stdout).unwrap().trim();
-        let mut path = PathBuf::new();
-        path.push(git_root);
-        path.push("ressources/bios/dmg_boot.bin");
-        println!("path: {:?}", path);
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../ressources/bios/dmg_boot.bin");
         let data = fs::read(path).unwrap();
         Bios { data }
     }
@@ -32,6 +32,8 @@ pub const LY_COMPARE: u16 = 0xFF45;
 pub const YWINDOW: u16 = 0xFF4A;
 pub const XWINDOW: u16 = 0xFF4B;
 pub const BGP: u16 = 0xFF47;
+pub const OBP0: u16 = 0xFF48;
+pub const OBP1: u16 = 0xFF49;
 
 // Timer
 pub const DIV: u16 = 0xFF04;
@@ -0,0 +1,230 @@
+use crate::consts::{DMA_LEN, OAM_MIN};
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
+
+/// T-cycles per byte copied, the DMA moves one byte per M-cycle
+const CYCLES_PER_BYTE: u8 = 4;
+/// T-cycles between the write to FF46 and the first byte copied
+const STARTUP_CYCLES: u8 = 4;
+/// Last page a transfer reads, E0-FF are mapped to the echo RAM
+const LAST_SOURCE: u16 = 0xDF00;
+
+/// OAM DMA: the page written in FF46 is copied to OAM in the background,
+/// `Memory::clock_tick` does the copies returned by `tick`
+#[derive(Debug, Default)]
+pub struct Dma {
+    /// Last value written in FF46, read back as is
+    register: u8,
+    /// A transfer written but not started yet: the source and the cycles left
+    pending: Option<(u16, u8)>,
+    /// The transfer running: the source and the next byte to copy
+    running: Option<(u16, usize)>,
+    /// T-cycles into the current M-cycle of the running transfer
+    cycles: u8,
+}
+
+impl Dma {
+    pub fn get(&self) -> u8 {
+        self.register
+    }
+
+    /// Writing FF46 (re)starts a transfer after the startup delay, a transfer
+    /// already running goes on until then
+    pub fn set(&mut self, data: u8) {
+        self.register = data;
+        // The pages E0-FF are read from the echo RAM
+        let page = if data >= 0xE0 { data - 0x20 } else { data };
+        self.pending = Some(((page as u16) << 8, STARTUP_CYCLES));
+    }
+
+    /// The CPU only reaches HRAM and the IO registers while a transfer runs
+    pub fn blocks(&self, address: u16) -> bool {
+        self.running.is_some() && address < 0xFF00
+    }
+
+    /// Move the transfer a T-cycle, returns the source and destination of the
+    /// byte to copy on this cycle
+    pub fn tick(&mut self) -> Option<(u16, u16)> {
+        match self.pending {
+            Some((source, 1)) => {
+                self.pending = None;
+                self.running = Some((source, 0));
+                self.cycles = 0;
+                return None;
+            }
+            Some((source, cycles)) => self.pending = Some((source, cycles - 1)),
+            None => {}
+        }
+        let (source, index) = self.running?;
+        self.cycles += 1;
+        if self.cycles < CYCLES_PER_BYTE {
+            return None;
+        }
+        self.cycles = 0;
+        self.running = (index + 1 < DMA_LEN).then_some((source, index + 1));
+        Some((source + index as u16, OAM_MIN + index as u16))
+    }
+}
+
+impl Snapshot for Dma {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.u8(self.register);
+        writer.bool(self.pending.is_some());
+        if let Some((source, cycles)) = self.pending {
+            writer.u16(source);
+            writer.u8(cycles);
+        }
+        writer.bool(self.running.is_some());
+        if let Some((source, index)) = self.running {
+            writer.u16(source);
+            writer.usize(index);
+        }
+        writer.u8(self.cycles);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.register = reader.u8()?;
+        self.pending = match reader.bool()? {
+            true => Some((reader.u16()?, reader.u8()?)),
+            false => None,
+        };
+        self.running = match reader.bool()? {
+            true => Some((reader.u16()?, reader.usize()?)),
+            false => None,
+        };
+        self.cycles = reader.u8()?;
+        let pending = self.pending.is_some_and(|(source, cycles)| {
+            source > LAST_SOURCE || !(1..=STARTUP_CYCLES).contains(&cycles)
+        });
+        let running = self
+            .running
+            .is_some_and(|(source, index)| source > LAST_SOURCE || index >= DMA_LEN);
+        if pending || running || self.cycles >= CYCLES_PER_BYTE {
+            return Err(Error::InvalidState);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_dma {
+    use super::Dma;
+    use shared::snapshot::{Reader, Snapshot, Writer};
+    use shared::Error;
+
+    fn restore(dma: &Dma) -> Result<Dma, Error> {
+        let mut writer = Writer::new();
+        dma.snapshot(&mut writer);
+        let data = writer.finish();
+        let mut restored = Dma::default();
+        restored.restore(&mut Reader::new(&data))?;
+        Ok(restored)
+    }
+
+    /// Ticks until the transfer is over, with the cycle of each copy
+    fn run(dma: &mut Dma) -> Vec<(usize, (u16, u16))> {
+        (1..1000)
+            .filter_map(|cycle| dma.tick().map(|copy| (cycle, copy)))
+            .collect()
+    }
+
+    #[test]
+    fn test_one_byte_per_m_cycle() {
+        let mut dma = Dma::default();
+        dma.set(0xC1);
+
+        let copies = run(&mut dma);
+        assert_eq!(copies.len(), 160);
+        assert_eq!(copies[0], (8, (0xC100, 0xFE00)));
+        assert_eq!(copies[159], (644, (0xC19F, 0xFE9F)));
+        assert_eq!(dma.get(), 0xC1);
+    }
+
+    #[test]
+    fn test_blocks_after_startup() {
+        let mut dma = Dma::default();
+        dma.set(0xC0);
+
+        assert!(!dma.blocks(0xC000));
+        for _ in 0..4 {
+            dma.tick();
+        }
+        assert!(dma.blocks(0xC000));
+        assert!(dma.blocks(0xFE00));
+        assert!(!dma.blocks(0xFF46));
+        assert!(!dma.blocks(0xFF80));
+
+        run(&mut dma);
+        assert!(!dma.blocks(0xC000));
+    }
+
+    #[test]
+    fn test_restart() {
+        let mut dma = Dma::default();
+        dma.set(0xC0);
+        for _ in 0..97 {
+            dma.tick();
+        }
+
+        dma.set(0xD0);
+        let old = (0..3).filter_map(|_| dma.tick()).collect::<Vec<_>>();
+        assert!(dma.blocks(0xC000));
+        assert_eq!(old, vec![(0xC017, 0xFE17)]);
+        dma.tick();
+        let copies = run(&mut dma);
+        assert_eq!(copies.len(), 160);
+        assert_eq!(copies[0].1, (0xD000, 0xFE00));
+    }
+
+    #[test]
+    fn test_echo_source() {
+        let mut dma = Dma::default();
+        dma.set(0xE1);
+
+        assert_eq!(run(&mut dma)[0].1, (0xC100, 0xFE00));
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut dma = Dma::default();
+        dma.set(0xC0);
+        for _ in 0..99 {
+            dma.tick();
+        }
+
+        let mut restored = restore(&dma).unwrap();
+        assert_eq!(restored.tick(), dma.tick());
+        assert_eq!(run(&mut restored), run(&mut dma));
+    }
+
+    #[test]
+    fn test_restore_invalid() {
+        let states = [
+            Dma {
+                pending: Some((0xC000, 0)),
+                ..Default::default()
+            },
+            Dma {
+                pending: Some((0xC000, 5)),
+                ..Default::default()
+            },
+            Dma {
+                running: Some((0xFF00, 0)),
+                ..Default::default()
+            },
+            Dma {
+                running: Some((0xC000, 160)),
+                ..Default::default()
+            },
+            Dma {
+                running: Some((0xC000, 0)),
+                cycles: 4,
+                ..Default::default()
+            },
+        ];
+
+        for state in states {
+            assert!(restore(&state).is_err());
+        }
+    }
+}
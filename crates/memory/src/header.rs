@@ -8,7 +8,11 @@ mod size;
 mod title;
 
 use crate::Cartridge;
-use consts::{ENTRY_LEN, LOGO_LEN, NEW_LICENSE_LEN, TITLE_LEN};
+pub use consts::HEADER_LEN;
+use consts::{
+    CARTRIDGE_TYPE, CHECKSUM, CHECKSUM_START, ENTRY_LEN, HEADER_START, LOGO_LEN, MMM01_MENU_LEN,
+    NEW_LICENSE_LEN, TITLE_LEN,
+};
 use destination::Destination;
 use error::Error;
 use flag::Sgb;
@@ -42,6 +46,27 @@ fn extract<T, const N: usize>(v: &mut Vec<T>) -> [T; N] {
         .unwrap_or_else(|v: Vec<T>| panic!("Expected a Vec of length {} but it was {}", N, v.len()))
 }
 
+impl Header {
+    /// Offset of the header in the ROM. MMM01 multicarts keep the header of
+    /// their menu at the end of the image, bank 0 holding the first sub-game.
+    pub fn locate(rom: &[u8]) -> usize {
+        let valid = |start: usize| {
+            rom.get(start..start + HEADER_LEN).is_some_and(|raw| {
+                let checksum = raw[CHECKSUM_START..CHECKSUM]
+                    .iter()
+                    .fold(0u8, |x, byte| x.wrapping_sub(*byte).wrapping_sub(1));
+                checksum == raw[CHECKSUM]
+            })
+        };
+        let menu = rom.len().saturating_sub(MMM01_MENU_LEN) + HEADER_START;
+        let mmm01 = Cartridge::Mmm01 as u8..=Cartridge::Mmm01RamBattery as u8;
+        match rom.get(menu + CARTRIDGE_TYPE) {
+            Some(cartridge) if mmm01.contains(cartridge) && valid(menu) => menu,
+            _ => HEADER_START,
+        }
+    }
+}
+
 impl TryFrom<Vec<u8>> for Header {
     type Error = Error;
 
@@ -78,3 +103,45 @@ impl TryFrom<Vec<u8>> for Header {
         })
     }
 }
+
+#[cfg(test)]
+mod test_header {
+    use super::Header;
+
+    /// A header with a valid checksum for the given cartridge type
+    fn raw_header(cartridge: u8) -> Vec<u8> {
+        let mut raw = vec![0; 0x50];
+        raw[0x47] = cartridge;
+        raw[0x4d] = raw[0x34..0x4d]
+            .iter()
+            .fold(0u8, |x, byte| x.wrapping_sub(*byte).wrapping_sub(1));
+        raw
+    }
+
+    #[test]
+    fn test_locate_header_in_bank_0() {
+        let mut rom = vec![0; 0x10000];
+        rom[0x100..0x150].copy_from_slice(&raw_header(0x01));
+
+        assert_eq!(Header::locate(&rom), 0x100);
+    }
+
+    #[test]
+    fn test_locate_mmm01_header_at_the_end() {
+        let mut rom = vec![0; 0x20000];
+        rom[0x100..0x150].copy_from_slice(&raw_header(0x01));
+        rom[0x18100..0x18150].copy_from_slice(&raw_header(0x0d));
+
+        assert_eq!(Header::locate(&rom), 0x18100);
+    }
+
+    #[test]
+    fn test_locate_ignores_invalid_checksum() {
+        let mut rom = vec![0; 0x20000];
+        let mut header = raw_header(0x0b);
+        header[0x4d] ^= 0xff;
+        rom[0x18100..0x18150].copy_from_slice(&header);
+
+        assert_eq!(Header::locate(&rom), 0x100);
+    }
+}
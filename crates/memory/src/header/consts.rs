@@ -2,3 +2,12 @@ pub const ENTRY_LEN: usize = 4;
 pub const LOGO_LEN: usize = 48;
 pub const TITLE_LEN: usize = 16;
 pub const NEW_LICENSE_LEN: usize = 2;
+pub const HEADER_START: usize = 0x100;
+pub const HEADER_LEN: usize = 0x50;
+/// Cartridge type, relative to the header start
+pub const CARTRIDGE_TYPE: usize = 0x47;
+/// The header checksum covers the title up to the rom version
+pub const CHECKSUM_START: usize = 0x34;
+pub const CHECKSUM: usize = 0x4D;
+/// MMM01 multicarts boot from the last 32 KiB of the ROM
+pub const MMM01_MENU_LEN: usize = 0x8000;
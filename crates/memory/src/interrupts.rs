@@ -1,3 +1,4 @@
+use shared::snapshot::{Reader, Snapshot, Writer};
 use shared::Error;
 use shared::Interrupt;
 use shared::Interrupts as Registered;
@@ -123,6 +124,25 @@ impl Interrupts {
     }
 }
 
+/// IF is shared with the components raising interrupts, it is restored in place
+impl Snapshot for Interrupts {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.u8(self.is_interrupted);
+        writer.u8(self.is_disabled);
+        writer.bool(self.master_enabled);
+        writer.u8(self.get_enabled().unwrap());
+        writer.u8(self.get_requested().unwrap());
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.is_interrupted = reader.u8()?;
+        self.is_disabled = reader.u8()?;
+        self.master_enabled = reader.bool()?;
+        self.set_enabled(reader.u8()?)?;
+        self.set_requested(reader.u8()?)
+    }
+}
+
 #[cfg(test)]
 mod test_interrupts {
     use super::*;
@@ -1,6 +1,7 @@
 use crate::{consts, Area};
 use crate::{Joypad, Serial, Timer};
 use apu::Apu;
+use shared::snapshot::{Reader, Snapshot, Writer};
 use shared::{Error, Interrupts};
 
 #[derive(Debug)]
@@ -57,3 +58,19 @@ impl IO {
         self.timer.tick()
     }
 }
+
+impl Snapshot for IO {
+    fn snapshot(&self, writer: &mut Writer) {
+        self.joypad.snapshot(writer);
+        self.timer.snapshot(writer);
+        self.serial.snapshot(writer);
+        writer.bytes(&self.temp);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.joypad.restore(reader)?;
+        self.timer.restore(reader)?;
+        self.serial.restore(reader)?;
+        reader.bytes_into(&mut self.temp)
+    }
+}
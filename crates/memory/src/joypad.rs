@@ -1,3 +1,5 @@
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
 use shared::{Interrupt, Interrupts};
 
 const SELECT: u8 = 0x30;
@@ -95,3 +97,18 @@ impl Joypad {
         self.update();
     }
 }
+
+impl Snapshot for Joypad {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.u8(self.actions);
+        writer.u8(self.directions);
+        writer.u8(self.data);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.actions = reader.u8()?;
+        self.directions = reader.u8()?;
+        self.data = reader.u8()?;
+        Ok(())
+    }
+}
@@ -3,6 +3,7 @@ pub mod r#async;
 pub(crate) mod bios;
 mod bus;
 pub(crate) mod consts;
+pub(crate) mod dma;
 pub mod futures;
 pub mod header;
 pub mod interface;
@@ -13,6 +14,7 @@ pub(crate) mod mbc;
 pub mod memory;
 pub(crate) mod ppu;
 pub(crate) mod ram;
+pub mod save;
 pub(crate) mod serial;
 pub mod state;
 pub(crate) mod timer;
@@ -23,8 +25,9 @@ pub use futures::{Getter, Setter};
 pub use header::Header;
 pub use interface::{Bus, Memory, Rom};
 pub use joypad::{Joypad, JoypadKey};
-pub use mbc::Cartridge;
+pub use mbc::{Cartridge, Infrared, InfraredLink};
 pub use r#async::Async;
+pub use save::{SaveFile, SaveKey, SaveManager, Slots};
 pub use serial::Serial;
 pub use state::State;
 pub use timer::Timer;
@@ -1,20 +1,32 @@
 pub mod bus;
+pub mod camera;
 pub(super) mod cartridge;
 pub(super) mod consts;
 pub(crate) mod default;
+pub(super) mod huc1;
+pub(super) mod huc3;
+pub mod infrared;
 pub(super) mod mbc0;
 pub(super) mod mbc1;
-//pub(super) mod mbc2;
-//pub(super) mod mbc3;
-//pub(super) mod mbc5;
+pub(super) mod mbc2;
+pub(super) mod mbc3;
+pub(super) mod mbc5;
+pub(super) mod mbc7;
+pub(super) mod mmm01;
 //pub mod mode;
 
 pub use bus::Mbc;
+pub use camera::PocketCamera;
 pub use cartridge::Cartridge;
+pub use huc1::HuC1;
+pub use huc3::HuC3;
+pub use infrared::{Infrared, InfraredLink};
 pub use mbc0::Mbc0;
 pub use mbc1::Mbc1;
-//pub use mbc2::Mbc2;
-//pub use mbc3::Mbc3;
-//pub use mbc5::Mbc5;
+pub use mbc2::Mbc2;
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use mbc7::Mbc7;
+pub use mmm01::Mmm01;
 
 //pub use mode::MbcMode;
@@ -1,8 +1,43 @@
+use super::infrared::Infrared;
+use shared::snapshot::Snapshot;
 use shared::Error;
 
-pub trait Mbc: std::fmt::Debug + AsRef<Vec<u8>> {
+/// Bank registers and RAM are part of the save states through `Snapshot`
+pub trait Mbc: std::fmt::Debug + AsRef<Vec<u8>> + Snapshot {
     fn get_rom(&self, _: usize) -> Result<u8, Error>;
     fn set_rom(&mut self, address: usize, data: u8) -> Result<(), Error>;
     fn get_ram(&self, _: usize) -> Result<u8, Error>;
     fn set_ram(&mut self, address: usize, data: u8) -> Result<(), Error>;
+
+    /// State of the rumble motor, for the cartridges that have one
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    /// Host tilt in g on both axis, for the cartridges with an accelerometer
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+
+    /// Advance the cartridge hardware by one T-cycle
+    fn tick(&mut self) {}
+
+    /// Grayscale image seen by the cartridges with a camera sensor
+    fn set_sensor(&mut self, _image: Vec<u8>) {}
+
+    /// Photos saved in the cartridge RAM in album order, one shade (0 ..= 3)
+    /// per pixel
+    fn photos(&self) -> Vec<Vec<u8>> {
+        Vec::new()
+    }
+
+    /// Plug the other end of the infrared port, for the cartridges that have one
+    fn set_infrared(&mut self, _peer: Box<dyn Infrared>) {}
+
+    /// Battery backed RAM in the common `.sav` layout, followed by the RTC
+    /// footer for the cartridges with a clock. `None` without a battery.
+    fn battery(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Restore the battery backed RAM from a `.sav` file
+    fn load_battery(&mut self, _data: Vec<u8>) {}
 }
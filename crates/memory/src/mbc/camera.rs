@@ -0,0 +1,429 @@
+use super::bus::Mbc;
+use super::consts;
+use crate::{Cartridge, Header};
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
+use std::convert::AsRef;
+use std::{fs, io, path};
+
+const WIDTH: usize = consts::CAMERA_WIDTH;
+const HEIGHT: usize = consts::CAMERA_HEIGHT;
+
+/// Game Boy Camera, the sensor registers are mapped in A000-BFFF when bit 4
+/// of the RAM bank register is set.
+#[derive(Debug)]
+pub struct PocketCamera {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    /// Only writes are gated, RAM can always be read
+    ram_on: bool,
+    /// Max 64 0x00 ..= 0x3f
+    rombank: usize,
+    /// Max 16 0x00 ..= 0x0f, 0x10 maps the sensor registers
+    rambank: usize,
+    /// A000 => capture / busy, A001-A005 => sensor settings, A006-A035 => dither matrix
+    registers: Vec<u8>,
+    /// T-cycles before the capture in progress lands in RAM
+    countdown: u32,
+    /// Grayscale, 0 is black and 255 is white
+    sensor: Vec<u8>,
+    /// The RAM is kept by a battery, see `save::SaveFile`
+    battery: bool,
+}
+
+impl Default for PocketCamera {
+    fn default() -> Self {
+        PocketCamera {
+            rom: vec![0; consts::CAMERA_MAX_SIZE],
+            ram: vec![0; consts::CAMERA_RAM_SIZE],
+            ram_on: false,
+            rombank: 1,
+            rambank: 0,
+            registers: vec![0; consts::CAMERA_REGISTERS],
+            countdown: 0,
+            sensor: test_pattern(),
+            battery: false,
+        }
+    }
+}
+
+impl AsRef<Vec<u8>> for PocketCamera {
+    fn as_ref(&self) -> &Vec<u8> {
+        self.rom.as_ref()
+    }
+}
+
+impl Mbc for PocketCamera {
+    fn get_rom(&self, address: usize) -> Result<u8, Error> {
+        let index = if address < 0x4000 {
+            address
+        } else {
+            (self.rombank * 0x4000) | (address & 0x3FFF)
+        };
+        Ok(*self.rom.get(index).unwrap_or(&0xFF))
+    }
+
+    fn set_rom(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_on = data & 0x0F == 0x0A;
+            }
+            0x2000..=0x3FFF => {
+                self.rombank = (data as usize) & 0x3F;
+            }
+            0x4000..=0x5FFF => {
+                self.rambank = (data as usize) & 0x1F;
+            }
+            0x6000..=0x7FFF => {}
+            _ => return Err(Error::IllegalSet(address, data)),
+        };
+        Ok(())
+    }
+
+    /// Only the capture register can be read back from the sensor
+    fn get_ram(&self, address: usize) -> Result<u8, Error> {
+        if self.rambank & 0x10 != 0 {
+            return Ok(match address & 0x7F {
+                0 => self.registers[0],
+                _ => 0x00,
+            });
+        }
+        Ok(*self
+            .ram
+            .get((self.rambank * 0x2000) | (address & 0x1FFF))
+            .unwrap_or(&0xFF))
+    }
+
+    fn set_ram(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        if self.rambank & 0x10 != 0 {
+            self.set_register(address & 0x7F, data);
+            return Ok(());
+        }
+        if !self.ram_on {
+            return Ok(());
+        }
+        if let Some(byte) = self
+            .ram
+            .get_mut((self.rambank * 0x2000) | (address & 0x1FFF))
+        {
+            *byte = data;
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        if self.countdown == 0 {
+            return;
+        }
+        self.countdown -= 1;
+        if self.countdown == 0 {
+            self.capture();
+            self.registers[0] &= !0x01;
+        }
+    }
+
+    fn set_sensor(&mut self, image: Vec<u8>) {
+        self.sensor = image;
+        self.sensor.resize(WIDTH * HEIGHT, 0xFF);
+    }
+
+    /// Only the slots the game lists in its photo table, in album order
+    fn photos(&self) -> Vec<Vec<u8>> {
+        let table = &self.ram[consts::CAMERA_PHOTO_TABLE..][..consts::CAMERA_PHOTOS];
+        let mut slots: Vec<(u8, usize)> = table
+            .iter()
+            .enumerate()
+            .filter(|(_, position)| **position != 0xFF)
+            .map(|(slot, position)| (*position, slot))
+            .collect();
+        slots.sort_unstable();
+        slots
+            .into_iter()
+            .map(|(_, slot)| consts::CAMERA_PHOTO_START + slot * consts::CAMERA_PHOTO_STRIDE)
+            .map(|start| decode(&self.ram[start..start + consts::CAMERA_PHOTO_SIZE]))
+            .collect()
+    }
+
+    fn battery(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_battery(&mut self, mut data: Vec<u8>) {
+        data.resize(self.ram.len(), 0);
+        self.ram = data;
+    }
+}
+
+impl Snapshot for PocketCamera {
+    /// The sensor image comes from the host and is not part of the state
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.bytes(&self.ram);
+        writer.bool(self.ram_on);
+        writer.usize(self.rombank);
+        writer.usize(self.rambank);
+        writer.bytes(&self.registers);
+        writer.u32(self.countdown);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        reader.bytes_into(&mut self.ram)?;
+        self.ram_on = reader.bool()?;
+        self.rombank = reader.usize()?;
+        self.rambank = reader.usize()?;
+        reader.bytes_into(&mut self.registers)?;
+        self.countdown = reader.u32()?;
+        Ok(())
+    }
+}
+
+impl PocketCamera {
+    pub fn new(header: Header, data: Vec<u8>) -> Box<Self> {
+        let battery = matches!(header.cartridge, Cartridge::PocketCamera);
+
+        let res = PocketCamera {
+            rom: data,
+            ram: vec![0; consts::CAMERA_RAM_SIZE],
+            ram_on: false,
+            rombank: 1,
+            rambank: 0,
+            registers: vec![0; consts::CAMERA_REGISTERS],
+            countdown: 0,
+            sensor: test_pattern(),
+            battery,
+        };
+        Box::new(res)
+    }
+
+    /// Writing bit 0 of A000 starts a capture, which lasts
+    /// 32446 + (N ? 0 : 512) + 16 * exposure M-cycles.
+    fn set_register(&mut self, register: usize, data: u8) {
+        match register {
+            0 => {
+                let busy = self.countdown != 0;
+                self.registers[0] = (data & 0x07) | busy as u8;
+                if data & 0x01 != 0 && !busy {
+                    let n = self.registers[1] & 0x80 != 0;
+                    let cycles = 32446 + if n { 0 } else { 512 } + 16 * self.exposure();
+                    self.countdown = cycles * 4;
+                }
+            }
+            1..=0x35 => self.registers[register] = data,
+            _ => {}
+        }
+    }
+
+    fn exposure(&self) -> u32 {
+        (self.registers[2] as u32) << 8 | self.registers[3] as u32
+    }
+
+    /// Simplified sensor: the exposure scales the brightness, A004 bit 3
+    /// inverts it, then each pixel is compared to the 3 thresholds of its
+    /// 4x4 dither matrix cell to pick one of the 4 shades.
+    fn capture(&mut self) {
+        let exposure = self.exposure();
+        let invert = self.registers[4] & 0x08 != 0;
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let value = self.sensor[y * WIDTH + x] as u32 * exposure;
+                let value = (value / consts::CAMERA_EXPOSURE).min(0xFF) as u8;
+                let value = if invert { !value } else { value };
+                let cell = 6 + ((y & 3) * 4 + (x & 3)) * 3;
+                let thresholds = &self.registers[cell..cell + 3];
+                let shade = thresholds.iter().filter(|t| value < **t).count() as u8;
+
+                let tile = (y / 8) * (WIDTH / 8) + x / 8;
+                let offset = consts::CAMERA_CAPTURE_START + tile * 16 + (y % 8) * 2;
+                let bit = 7 - (x % 8);
+                self.ram[offset] = (self.ram[offset] & !(1 << bit)) | (shade & 1) << bit;
+                self.ram[offset + 1] =
+                    (self.ram[offset + 1] & !(1 << bit)) | ((shade >> 1) & 1) << bit;
+            }
+        }
+    }
+}
+
+/// Synthetic sensor image: a diagonal gradient with a checkerboard on top,
+/// so captures are deterministic without any host camera.
+fn test_pattern() -> Vec<u8> {
+    (0..HEIGHT)
+        .flat_map(|y| {
+            (0..WIDTH).map(move |x| {
+                let gradient = ((x + y) * 0xFF / (WIDTH + HEIGHT - 2)) as u8;
+                if (x / 16 + y / 16) % 2 == 0 {
+                    gradient
+                } else {
+                    gradient / 2
+                }
+            })
+        })
+        .collect()
+}
+
+/// Convert 16x14 tiles of 2bpp data into one shade (0 ..= 3) per pixel
+fn decode(tiles: &[u8]) -> Vec<u8> {
+    (0..HEIGHT)
+        .flat_map(|y| {
+            (0..WIDTH).map(move |x| {
+                let tile = (y / 8) * (WIDTH / 8) + x / 8;
+                let offset = tile * 16 + (y % 8) * 2;
+                let bit = 7 - (x % 8);
+                ((tiles[offset] >> bit) & 1) | ((tiles[offset + 1] >> bit) & 1) << 1
+            })
+        })
+        .collect()
+}
+
+/// Load a PNG as the sensor image, scaled to 128x112 grayscale
+pub fn load_png(path: &path::Path) -> io::Result<Vec<u8>> {
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    let mut decoder = png::Decoder::new(fs::File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(invalid)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(invalid)?;
+    let channels = info.color_type.samples();
+    let (width, height) = (info.width as usize, info.height as usize);
+
+    Ok((0..HEIGHT)
+        .flat_map(|y| {
+            let buffer = &buffer;
+            (0..WIDTH).map(move |x| {
+                let pixel = ((y * height / HEIGHT) * width + x * width / WIDTH) * channels;
+                match channels {
+                    1 | 2 => buffer[pixel],
+                    _ => {
+                        let (r, g, b) = (buffer[pixel], buffer[pixel + 1], buffer[pixel + 2]);
+                        ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+                    }
+                }
+            })
+        })
+        .collect())
+}
+
+/// Write a photo, one shade per pixel, as a grayscale PNG
+pub fn save_png(path: &path::Path, shades: &[u8]) -> io::Result<()> {
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    let pixels: Vec<u8> = shades.iter().map(|shade| 0xFF - shade * 0x55).collect();
+    let mut encoder = png::Encoder::new(fs::File::create(path)?, WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(invalid)?;
+    writer.write_image_data(&pixels).map_err(invalid)
+}
+
+#[cfg(test)]
+mod camera_test {
+    use super::{consts, decode, load_png, save_png, Mbc, PocketCamera};
+
+    fn setup_camera() -> PocketCamera {
+        let mut mbc = PocketCamera::default();
+        mbc.set_rom(0x0000, 0x0a).unwrap();
+        mbc.set_rom(0x4000, 0x10).unwrap();
+        mbc.set_ram(0xa002, (consts::CAMERA_EXPOSURE >> 8) as u8)
+            .unwrap();
+        mbc.set_ram(0xa003, consts::CAMERA_EXPOSURE as u8).unwrap();
+        for cell in 0..16 {
+            mbc.set_ram(0xa006 + cell * 3, 0x40).unwrap();
+            mbc.set_ram(0xa007 + cell * 3, 0x80).unwrap();
+            mbc.set_ram(0xa008 + cell * 3, 0xc0).unwrap();
+        }
+        mbc
+    }
+
+    fn shoot(mbc: &mut PocketCamera) -> u32 {
+        mbc.set_ram(0xa000, 0x01).unwrap();
+        let mut cycles = 0;
+        while mbc.get_ram(0xa000).unwrap() & 0x01 != 0 {
+            mbc.tick();
+            cycles += 1;
+        }
+        cycles
+    }
+
+    fn capture(mbc: &PocketCamera) -> Vec<u8> {
+        let start = consts::CAMERA_CAPTURE_START;
+        decode(&mbc.ram[start..start + consts::CAMERA_PHOTO_SIZE])
+    }
+
+    #[test]
+    fn test_camera_ram_banks() {
+        let mut mbc = PocketCamera::default();
+
+        mbc.set_rom(0x0000, 0x0a).unwrap();
+        mbc.set_rom(0x4000, 0x0f).unwrap();
+        mbc.set_ram(0xbfff, 0x42).unwrap();
+        assert_eq!(mbc.ram[0x1ffff], 0x42);
+
+        mbc.set_rom(0x0000, 0x00).unwrap();
+        mbc.set_ram(0xbfff, 0x24).unwrap();
+        assert_eq!(mbc.get_ram(0xbfff).unwrap(), 0x42);
+    }
+
+    #[test]
+    fn test_camera_registers_mapped() {
+        let mbc = setup_camera();
+
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0x00);
+        assert_eq!(mbc.get_ram(0xa002).unwrap(), 0x00);
+        assert_eq!(mbc.ram[0x0002], 0x00);
+    }
+
+    #[test]
+    fn test_camera_capture_timing() {
+        let mut mbc = setup_camera();
+
+        let exposure = consts::CAMERA_EXPOSURE;
+        assert_eq!(shoot(&mut mbc), (32446 + 512 + 16 * exposure) * 4);
+
+        mbc.set_ram(0xa001, 0x80).unwrap();
+        assert_eq!(shoot(&mut mbc), (32446 + 16 * exposure) * 4);
+    }
+
+    #[test]
+    fn test_camera_capture_dithering() {
+        let mut mbc = setup_camera();
+        mbc.set_sensor(vec![0x00, 0x50, 0x90, 0xff]);
+
+        shoot(&mut mbc);
+        assert_eq!(&capture(&mbc)[0..5], &[3, 2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_camera_capture_invert() {
+        let mut mbc = setup_camera();
+        mbc.set_sensor(vec![0x00, 0x50, 0x90, 0xff]);
+        mbc.set_ram(0xa004, 0x08).unwrap();
+
+        shoot(&mut mbc);
+        assert_eq!(&capture(&mbc)[0..4], &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_camera_photos() {
+        let mut mbc = setup_camera();
+        mbc.ram[consts::CAMERA_PHOTO_START + consts::CAMERA_PHOTO_STRIDE] = 0x80;
+        mbc.ram[consts::CAMERA_PHOTO_START + consts::CAMERA_PHOTO_STRIDE + 1] = 0x80;
+        let table = &mut mbc.ram[consts::CAMERA_PHOTO_TABLE..][..consts::CAMERA_PHOTOS];
+        table.fill(0xFF);
+        table[1] = 0;
+        table[4] = 1;
+
+        let photos = mbc.photos();
+        assert_eq!(photos.len(), 2);
+        assert_eq!(photos[0][0], 3);
+        assert_eq!(photos[1][0], 0);
+    }
+
+    #[test]
+    fn test_camera_png_roundtrip() {
+        let path = std::env::temp_dir().join("gbmu_camera_test.png");
+        let shades: Vec<u8> = (0..128 * 112).map(|pixel| (pixel % 4) as u8).collect();
+
+        save_png(&path, &shades).unwrap();
+        let image = load_png(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(&image[0..4], &[0xff, 0xaa, 0x55, 0x00]);
+    }
+}
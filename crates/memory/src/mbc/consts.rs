@@ -20,36 +20,57 @@ pub const MBC0_MAX_SIZE: usize = 32_768;
 // pub const MBC1_REG2_END: usize = 0x5fff;
 // pub const MBC1_REG3_START: usize = 0x6000;
 // pub const MBC1_REG3_END: usize = 0x7fff;
-//
-// /// Controller for up to 2 Mbits (256 Kbytes) of ROM with built-in backup RAM (512 x 4 bits)
-// pub const MBC2_MAX_SIZE: usize = 262_144;
-// pub const MBC2_RAM_END: usize = 0xa1ff;
-// pub const MBC2_ERAM_START: usize = 0xa200;
-// pub const MBC2_ERAM_END: usize = 0xbfff;
-// pub const MBC2_MAGIC_BYTE: usize = 0x100;
-// pub const MBC2_REG_START: usize = 0x0;
-// pub const MBC2_REG_END: usize = 0x3fff;
-//
-// ///(max 2MByte ROM and/or 32KByte RAM and Timer)
-// pub const MBC3_MAX_SIZE: usize = 16_777_216;
-// pub const MBC3_RTC_OFFSET: usize = 0x0a;
-// pub const MBC3_REG0_START: usize = 0x0;
-// pub const MBC3_REG0_END: usize = 0x1fff;
-// pub const MBC3_REG1_START: usize = 0x2000;
-// pub const MBC3_REG1_END: usize = 0x3fff;
-// pub const MBC3_REG2_START: usize = 0x4000;
-// pub const MBC3_REG2_END: usize = 0x5fff;
-// pub const MBC3_REG3_START: usize = 0x6000;
-// pub const MBC3_REG3_END: usize = 0x7fff;
-// pub const MBC3_REG4_START: usize = 0xa000;
-// pub const MBC3_REG4_END: usize = 0xbfff;
-//
-// pub const MBC5_MAX_SIZE: usize = 8_388_608; // It can map up to 64 Mbits (8 MiB) of ROM.
-// pub const MBC5_REG0_START: usize = 0x0;
-// pub const MBC5_REG0_END: usize = 0x1fff;
-// pub const MBC5_REG1_START: usize = 0x2000;
-// pub const MBC5_REG1_END: usize = 0x2fff;
-// pub const MBC5_REG2_START: usize = 0x3000;
-// pub const MBC5_REG2_END: usize = 0x3fff;
-// pub const MBC5_REG3_START: usize = 0x4000;
-// pub const MBC5_REG3_END: usize = 0x5fff;
+
+/// Controller for up to 2 Mbits (256 Kbytes) of ROM with built-in backup RAM (512 x 4 bits)
+pub const MBC2_MAX_SIZE: usize = 262_144;
+pub const MBC2_RAM_SIZE: usize = 0x200;
+pub const MBC2_REGISTER_SELECT: usize = 0x100;
+
+///(max 2MByte ROM and/or 32KByte RAM and Timer)
+pub const MBC3_MAX_SIZE: usize = 2_097_152;
+pub const MBC3_RAM_SIZE: usize = 0x8000;
+pub const MBC3_RTC_FOOTER: usize = 48;
+/// The cartridge clocks count their seconds from the emulated T-cycles
+pub const CYCLES_PER_SECOND: u64 = 4_194_304;
+
+/// It can map up to 64 Mbits (8 MiB) of ROM and 128 KiB of RAM.
+pub const MBC5_MAX_SIZE: usize = 8_388_608;
+pub const MBC5_RAM_SIZE: usize = 0x20000;
+
+/// A multicart of up to 8 MiB of ROM and 128 KiB of RAM, the menu lives in
+/// the last 32 KiB of the ROM
+pub const MMM01_MAX_SIZE: usize = 8_388_608;
+pub const MMM01_RAM_SIZE: usize = 0x20000;
+pub const MMM01_MENU_SIZE: usize = 0x8000;
+
+/// Up to 2 MiB of ROM, a 2 axis accelerometer and a 256 bytes serial EEPROM
+pub const MBC7_MAX_SIZE: usize = 2_097_152;
+pub const MBC7_EEPROM_SIZE: usize = 256;
+pub const MBC7_ACCEL_ERASED: u16 = 0x8000;
+pub const MBC7_ACCEL_CENTER: u16 = 0x81D0;
+pub const MBC7_ACCEL_G: f32 = 112.0;
+
+/// Up to 1 MiB of ROM and 128 KiB of RAM, the sensor captures 128x112 pixels
+pub const CAMERA_MAX_SIZE: usize = 1_048_576;
+pub const CAMERA_RAM_SIZE: usize = 0x20000;
+pub const CAMERA_REGISTERS: usize = 0x36;
+pub const CAMERA_WIDTH: usize = 128;
+pub const CAMERA_HEIGHT: usize = 112;
+/// Exposure at which the sensor output is the image brightness
+pub const CAMERA_EXPOSURE: u32 = 0x0800;
+pub const CAMERA_CAPTURE_START: usize = 0x0100;
+pub const CAMERA_PHOTO_SIZE: usize = 0x0E00;
+pub const CAMERA_PHOTO_START: usize = 0x2000;
+pub const CAMERA_PHOTO_STRIDE: usize = 0x1000;
+pub const CAMERA_PHOTOS: usize = 30;
+/// Album position of each photo slot, 0xFF when the slot is free
+pub const CAMERA_PHOTO_TABLE: usize = 0x11B2;
+
+/// Up to 2 MiB of ROM and 32 KiB of RAM, with an IR port
+pub const HUC1_MAX_SIZE: usize = 2_097_152;
+pub const HUC1_RAM_SIZE: usize = 0x8000;
+
+/// Up to 2 MiB of ROM and 32 KiB of RAM, with an IR port and a clock
+pub const HUC3_MAX_SIZE: usize = 2_097_152;
+pub const HUC3_RAM_SIZE: usize = 0x8000;
+pub const HUC3_RTC_FOOTER: usize = 12;
@@ -0,0 +1,211 @@
+use super::bus::Mbc;
+use super::consts;
+use super::infrared::{Infrared, NoPeer};
+use crate::{Cartridge, Header};
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
+use std::convert::AsRef;
+
+#[derive(Debug)]
+pub struct HuC1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    /// Writing 0x0E in 0000-1FFF maps the IR port in A000-BFFF instead of the RAM
+    ir_mode: bool,
+    /// Max 64 0x01 ..= 0x3f
+    rombank: usize,
+    /// Max 4 0x00 ..= 0x03
+    rambank: usize,
+    infrared: Box<dyn Infrared>,
+    /// The RAM is kept by a battery, see `save::SaveFile`
+    battery: bool,
+}
+
+impl Default for HuC1 {
+    fn default() -> Self {
+        HuC1 {
+            rom: vec![0; consts::HUC1_MAX_SIZE],
+            ram: vec![0; consts::HUC1_RAM_SIZE],
+            ir_mode: false,
+            rombank: 1,
+            rambank: 0,
+            infrared: Box::new(NoPeer),
+            battery: false,
+        }
+    }
+}
+
+impl AsRef<Vec<u8>> for HuC1 {
+    fn as_ref(&self) -> &Vec<u8> {
+        self.rom.as_ref()
+    }
+}
+
+impl Mbc for HuC1 {
+    fn get_rom(&self, address: usize) -> Result<u8, Error> {
+        let index = if address < 0x4000 {
+            address
+        } else {
+            (self.rombank * 0x4000) | (address & 0x3FFF)
+        };
+        Ok(*self.rom.get(index).unwrap_or(&0xFF))
+    }
+
+    fn set_rom(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ir_mode = data & 0x0F == 0x0E;
+            }
+            0x2000..=0x3FFF => {
+                self.rombank = match (data as usize) & 0x3F {
+                    0 => 1,
+                    n => n,
+                }
+            }
+            0x4000..=0x5FFF => {
+                self.rambank = (data as usize) & 0x03;
+            }
+            0x6000..=0x7FFF => {}
+            _ => return Err(Error::IllegalSet(address, data)),
+        };
+        Ok(())
+    }
+
+    /// In IR mode bit 0 tells if light is received
+    fn get_ram(&self, address: usize) -> Result<u8, Error> {
+        if self.ir_mode {
+            return Ok(0xC0 | self.infrared.receive() as u8);
+        }
+        Ok(*self
+            .ram
+            .get((self.rambank * 0x2000) | (address & 0x1FFF))
+            .unwrap_or(&0xFF))
+    }
+
+    /// In IR mode bit 0 turns the LED on
+    fn set_ram(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        if self.ir_mode {
+            self.infrared.emit(data & 0x01 != 0);
+            return Ok(());
+        }
+        if let Some(byte) = self
+            .ram
+            .get_mut((self.rambank * 0x2000) | (address & 0x1FFF))
+        {
+            *byte = data;
+        }
+        Ok(())
+    }
+
+    fn set_infrared(&mut self, peer: Box<dyn Infrared>) {
+        self.infrared = peer;
+    }
+
+    fn battery(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_battery(&mut self, mut data: Vec<u8>) {
+        data.resize(self.ram.len(), 0);
+        self.ram = data;
+    }
+}
+
+impl Snapshot for HuC1 {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.bytes(&self.ram);
+        writer.bool(self.ir_mode);
+        writer.usize(self.rombank);
+        writer.usize(self.rambank);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        reader.bytes_into(&mut self.ram)?;
+        self.ir_mode = reader.bool()?;
+        self.rombank = reader.usize()?;
+        self.rambank = reader.usize()?;
+        Ok(())
+    }
+}
+
+impl HuC1 {
+    pub fn new(header: Header, data: Vec<u8>) -> Box<Self> {
+        let battery = matches!(header.cartridge, Cartridge::HuC1RamBattery);
+
+        let res = HuC1 {
+            rom: data,
+            ram: vec![0; header.ram_size.get_size()],
+            ir_mode: false,
+            rombank: 1,
+            rambank: 0,
+            infrared: Box::new(NoPeer),
+            battery,
+        };
+        Box::new(res)
+    }
+}
+
+#[cfg(test)]
+mod huc1_test {
+    use super::super::infrared::InfraredLink;
+    use super::{HuC1, Mbc};
+
+    fn setup_huc1() -> HuC1 {
+        HuC1 {
+            rom: (0..64u8).flat_map(|bank| vec![bank; 0x4000]).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_huc1_rom_bank() {
+        let mut mbc = setup_huc1();
+
+        mbc.set_rom(0x2000, 0x00).unwrap();
+        assert_eq!(mbc.get_rom(0x4000).unwrap(), 0x01);
+
+        mbc.set_rom(0x2000, 0x7f).unwrap();
+        assert_eq!(mbc.get_rom(0x7fff).unwrap(), 0x3f);
+    }
+
+    #[test]
+    fn test_huc1_ram_banks() {
+        let mut mbc = setup_huc1();
+
+        mbc.set_rom(0x4000, 0x03).unwrap();
+        mbc.set_ram(0xa000, 0x42).unwrap();
+        mbc.set_rom(0x4000, 0x00).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0x00);
+        mbc.set_rom(0x4000, 0x03).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0x42);
+    }
+
+    #[test]
+    fn test_huc1_ir_mode() {
+        let mut mbc = setup_huc1();
+
+        mbc.set_ram(0xa000, 0x42).unwrap();
+        mbc.set_rom(0x0000, 0x0e).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0xc0);
+
+        mbc.set_rom(0x0000, 0x0a).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0x42);
+    }
+
+    #[test]
+    fn test_huc1_ir_link() {
+        let (left, right) = InfraredLink::pair();
+        let mut sender = setup_huc1();
+        let mut receiver = setup_huc1();
+        sender.set_infrared(Box::new(left));
+        receiver.set_infrared(Box::new(right));
+        sender.set_rom(0x0000, 0x0e).unwrap();
+        receiver.set_rom(0x0000, 0x0e).unwrap();
+
+        sender.set_ram(0xa000, 0x01).unwrap();
+        assert_eq!(receiver.get_ram(0xa000).unwrap(), 0xc1);
+
+        sender.set_ram(0xa000, 0x00).unwrap();
+        assert_eq!(receiver.get_ram(0xa000).unwrap(), 0xc0);
+    }
+}
@@ -0,0 +1,431 @@
+use super::bus::Mbc;
+use super::consts;
+use super::infrared::{Infrared, NoPeer};
+use super::mbc3::get_epoch;
+use crate::{Cartridge, Header};
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
+use std::convert::{AsRef, TryInto};
+
+#[derive(Debug)]
+pub struct HuC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    /// [Mode]  [A000-BFFF]
+    /// 0x0     RAM, read only
+    /// 0xA     RAM, read / write
+    /// 0xB     RTC command
+    /// 0xC     RTC response
+    /// 0xD     RTC semaphore
+    /// 0xE     IR port
+    mode: u8,
+    /// Max 128 0x01 ..= 0x7f
+    rombank: usize,
+    /// Max 4 0x00 ..= 0x03
+    rambank: usize,
+    rtc: HuC3Rtc,
+    infrared: Box<dyn Infrared>,
+    /// The RAM is kept by a battery, see `save::SaveFile`
+    battery: bool,
+    /// T-cycles since the cartridge was powered on, the RTC time
+    cycles: u64,
+}
+
+impl Default for HuC3 {
+    fn default() -> Self {
+        HuC3 {
+            rom: vec![0; consts::HUC3_MAX_SIZE],
+            ram: vec![0; consts::HUC3_RAM_SIZE],
+            mode: 0,
+            rombank: 1,
+            rambank: 0,
+            rtc: HuC3Rtc::default(),
+            infrared: Box::new(NoPeer),
+            battery: false,
+            cycles: 0,
+        }
+    }
+}
+
+impl AsRef<Vec<u8>> for HuC3 {
+    fn as_ref(&self) -> &Vec<u8> {
+        self.rom.as_ref()
+    }
+}
+
+/// The clock only counts minutes and days, the game talks to it through
+/// commands that read and write a 256 nibbles memory.
+#[derive(Debug, Clone)]
+struct HuC3Rtc {
+    memory: Vec<u8>,
+    address: u8,
+    command: u8,
+    response: u8,
+    /// 0 ..= 1439
+    minutes: u16,
+    /// 12 bits wide
+    days: u16,
+    /// Emulated time in seconds at which the current minute started, host
+    /// time in the `.sav` footer
+    epoch: u64,
+}
+
+impl Default for HuC3Rtc {
+    fn default() -> Self {
+        HuC3Rtc {
+            memory: vec![0; 0x100],
+            address: 0,
+            command: 0,
+            response: 0,
+            minutes: 0,
+            days: 0,
+            epoch: 0,
+        }
+    }
+}
+
+impl HuC3Rtc {
+    fn update(&mut self, now: u64) {
+        let elapsed = now.saturating_sub(self.epoch) / 60;
+        self.epoch += elapsed * 60;
+        let minutes = self.minutes as u64 + elapsed;
+        self.minutes = (minutes % 1440) as u16;
+        self.days = ((self.days as u64 + minutes / 1440) & 0xFFF) as u16;
+    }
+
+    /// Run the clock until `from`, then count from `to` on, the seconds into
+    /// the current minute are kept
+    fn rebase(&mut self, from: u64, to: u64) {
+        self.update(from);
+        self.epoch = to.saturating_sub(from.saturating_sub(self.epoch));
+    }
+
+    /// [Command] [Argument]
+    /// 0x1       -           Read the nibble at the address, then increment it
+    /// 0x3       nibble      Write the nibble at the address, then increment it
+    /// 0x4       nibble      Low nibble of the address
+    /// 0x5       nibble      High nibble of the address
+    /// 0x6       0x0         Copy the time to 0x00-0x05
+    ///           0x1         Set the time from 0x00-0x05
+    ///           0x2         Status, always ready
+    fn execute(&mut self, data: u8, now: u64) {
+        let (command, argument) = ((data >> 4) & 0x07, data & 0x0F);
+        self.command = command;
+        match (command, argument) {
+            (0x1, _) => {
+                self.response = self.memory[self.address as usize];
+                self.address = self.address.wrapping_add(1);
+            }
+            (0x3, _) => {
+                self.memory[self.address as usize] = argument;
+                self.address = self.address.wrapping_add(1);
+            }
+            (0x4, _) => self.address = (self.address & 0xF0) | argument,
+            (0x5, _) => self.address = (self.address & 0x0F) | argument << 4,
+            (0x6, 0x0) => {
+                self.update(now);
+                for nibble in 0..3 {
+                    self.memory[nibble] = ((self.minutes >> (nibble * 4)) & 0x0F) as u8;
+                    self.memory[nibble + 3] = ((self.days >> (nibble * 4)) & 0x0F) as u8;
+                }
+            }
+            (0x6, 0x1) => {
+                let nibbles = |start: usize| {
+                    (0..3).fold(0u16, |value, nibble| {
+                        value | (self.memory[start + nibble] as u16 & 0x0F) << (nibble * 4)
+                    })
+                };
+                self.minutes = nibbles(0) % 1440;
+                self.days = nibbles(3);
+                self.epoch = now;
+            }
+            (0x6, 0x2) => self.response = 0x1,
+            _ => {}
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut footer = self.minutes.to_le_bytes().to_vec();
+        footer.extend(self.days.to_le_bytes());
+        footer.extend(self.epoch.to_le_bytes());
+        footer
+    }
+
+    fn load_bytes(&mut self, data: &[u8]) -> Option<()> {
+        if data.len() != consts::HUC3_RTC_FOOTER {
+            return None;
+        }
+        self.minutes = u16::from_le_bytes(data[0..2].try_into().ok()?);
+        self.days = u16::from_le_bytes(data[2..4].try_into().ok()?);
+        self.epoch = u64::from_le_bytes(data[4..12].try_into().ok()?);
+        Some(())
+    }
+}
+
+impl Mbc for HuC3 {
+    fn get_rom(&self, address: usize) -> Result<u8, Error> {
+        let index = if address < 0x4000 {
+            address
+        } else {
+            (self.rombank * 0x4000) | (address & 0x3FFF)
+        };
+        Ok(*self.rom.get(index).unwrap_or(&0xFF))
+    }
+
+    fn set_rom(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        match address {
+            0x0000..=0x1FFF => {
+                self.mode = data & 0x0F;
+            }
+            0x2000..=0x3FFF => {
+                self.rombank = match (data as usize) & 0x7F {
+                    0 => 1,
+                    n => n,
+                }
+            }
+            0x4000..=0x5FFF => {
+                self.rambank = (data as usize) & 0x03;
+            }
+            0x6000..=0x7FFF => {}
+            _ => return Err(Error::IllegalSet(address, data)),
+        };
+        Ok(())
+    }
+
+    fn get_ram(&self, address: usize) -> Result<u8, Error> {
+        Ok(match self.mode {
+            0x0 | 0xA => *self
+                .ram
+                .get((self.rambank * 0x2000) | (address & 0x1FFF))
+                .unwrap_or(&0xFF),
+            0xC => 0x80 | self.rtc.command << 4 | self.rtc.response,
+            0xD => 0x01,
+            0xE => 0xC0 | self.infrared.receive() as u8,
+            _ => 0xFF,
+        })
+    }
+
+    fn set_ram(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        match self.mode {
+            0xA => {
+                if let Some(byte) = self
+                    .ram
+                    .get_mut((self.rambank * 0x2000) | (address & 0x1FFF))
+                {
+                    *byte = data;
+                }
+            }
+            0xB => self.rtc.execute(data, self.now()),
+            0xE => self.infrared.emit(data & 0x01 != 0),
+            _ => {}
+        };
+        Ok(())
+    }
+
+    fn set_infrared(&mut self, peer: Box<dyn Infrared>) {
+        self.infrared = peer;
+    }
+
+    fn tick(&mut self) {
+        self.cycles += 1;
+    }
+
+    fn battery(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.save(get_epoch()))
+    }
+
+    /// The clock catches up with the host time spent since the save
+    fn load_battery(&mut self, data: Vec<u8>) {
+        self.load(data, get_epoch())
+    }
+}
+
+impl Snapshot for HuC3 {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.u64(self.cycles);
+        writer.bytes(&self.save(self.now()));
+        writer.u8(self.mode);
+        writer.usize(self.rombank);
+        writer.usize(self.rambank);
+        writer.bytes(&self.rtc.memory);
+        writer.u8(self.rtc.address);
+        writer.u8(self.rtc.command);
+        writer.u8(self.rtc.response);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.cycles = reader.u64()?;
+        let data = reader.bytes()?;
+        if data.len() != self.save(0).len() {
+            return Err(Error::InvalidState);
+        }
+        self.load(data, self.now());
+        self.mode = reader.u8()?;
+        self.rombank = reader.usize()?;
+        self.rambank = reader.usize()?;
+        reader.bytes_into(&mut self.rtc.memory)?;
+        self.rtc.address = reader.u8()?;
+        self.rtc.command = reader.u8()?;
+        self.rtc.response = reader.u8()?;
+        Ok(())
+    }
+}
+
+impl HuC3 {
+    pub fn new(header: Header, data: Vec<u8>) -> Box<Self> {
+        let battery = matches!(header.cartridge, Cartridge::HuC3);
+
+        let res = HuC3 {
+            rom: data,
+            ram: vec![0; header.ram_size.get_size()],
+            mode: 0,
+            rombank: 1,
+            rambank: 0,
+            rtc: HuC3Rtc::default(),
+            infrared: Box::new(NoPeer),
+            battery,
+            cycles: 0,
+        };
+        Box::new(res)
+    }
+
+    /// Seconds of emulated time, the clock the RTC counts from
+    fn now(&self) -> u64 {
+        self.cycles / consts::CYCLES_PER_SECOND
+    }
+
+    /// Battery RAM followed by the clock, brought up to date and stamped
+    /// with `epoch`
+    fn save(&self, epoch: u64) -> Vec<u8> {
+        let mut rtc = self.rtc.clone();
+        rtc.rebase(self.now(), epoch);
+        let mut data = self.ram.clone();
+        data.extend(rtc.to_bytes());
+        data
+    }
+
+    /// The saved clock runs until `epoch`, the time it is stamped with, then
+    /// goes on from the emulated time
+    fn load(&mut self, mut data: Vec<u8>, epoch: u64) {
+        let ramsize = self.ram.len();
+        let now = self.now();
+        let loaded = data
+            .get(ramsize..)
+            .and_then(|footer| self.rtc.load_bytes(footer));
+        if loaded.is_some() {
+            self.rtc.rebase(epoch, now);
+        }
+        data.resize(ramsize, 0);
+        self.ram = data;
+    }
+}
+
+#[cfg(test)]
+mod huc3_test {
+    use super::consts::CYCLES_PER_SECOND;
+    use super::{HuC3, Mbc};
+
+    fn setup_huc3() -> HuC3 {
+        let mut mbc = HuC3::default();
+        mbc.set_rom(0x0000, 0x0b).unwrap();
+        mbc
+    }
+
+    fn command(mbc: &mut HuC3, data: u8, now: u64) -> u8 {
+        mbc.set_rom(0x0000, 0x0b).unwrap();
+        mbc.rtc.execute(data, now);
+        mbc.set_rom(0x0000, 0x0c).unwrap();
+        mbc.get_ram(0xa000).unwrap()
+    }
+
+    /// Latch the time then read the 6 nibbles back
+    fn read_time(mbc: &mut HuC3, now: u64) -> (u16, u16) {
+        command(mbc, 0x60, now);
+        command(mbc, 0x40, now);
+        command(mbc, 0x50, now);
+        let nibbles: Vec<u16> = (0..6)
+            .map(|_| (command(mbc, 0x10, now) & 0x0f) as u16)
+            .collect();
+        (
+            nibbles[0] | nibbles[1] << 4 | nibbles[2] << 8,
+            nibbles[3] | nibbles[4] << 4 | nibbles[5] << 8,
+        )
+    }
+
+    #[test]
+    fn test_huc3_ram_modes() {
+        let mut mbc = HuC3::default();
+
+        mbc.set_rom(0x0000, 0x00).unwrap();
+        mbc.set_ram(0xa000, 0x42).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0x00);
+
+        mbc.set_rom(0x0000, 0x0a).unwrap();
+        mbc.set_ram(0xa000, 0x42).unwrap();
+        mbc.set_rom(0x0000, 0x00).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0x42);
+    }
+
+    #[test]
+    fn test_huc3_read_write_memory() {
+        let mut mbc = setup_huc3();
+
+        command(&mut mbc, 0x42, 0);
+        command(&mut mbc, 0x51, 0);
+        command(&mut mbc, 0x37, 0);
+        assert_eq!(mbc.rtc.memory[0x12], 0x07);
+        assert_eq!(mbc.rtc.address, 0x13);
+
+        command(&mut mbc, 0x42, 0);
+        assert_eq!(command(&mut mbc, 0x10, 0), 0x80 | 0x10 | 0x07);
+    }
+
+    #[test]
+    fn test_huc3_clock_runs() {
+        let mut mbc = setup_huc3();
+
+        assert_eq!(read_time(&mut mbc, 59), (0, 0));
+        assert_eq!(read_time(&mut mbc, 60 * 90 + 30), (90, 0));
+        assert_eq!(read_time(&mut mbc, 60 * (1440 * 3 + 5)), (5, 3));
+    }
+
+    #[test]
+    fn test_huc3_set_clock() {
+        let mut mbc = setup_huc3();
+
+        command(&mut mbc, 0x40, 1000);
+        command(&mut mbc, 0x50, 1000);
+        for nibble in [0xb, 0x9, 0x5, 0x2, 0x0, 0x0] {
+            command(&mut mbc, 0x30 | nibble, 1000);
+        }
+        command(&mut mbc, 0x61, 1000);
+
+        assert_eq!(read_time(&mut mbc, 1000 + 120), (0x59b + 2, 2));
+    }
+
+    #[test]
+    fn test_huc3_clock_counts_emulated_time() {
+        let mut mbc = setup_huc3();
+
+        mbc.cycles = 60 * 90 * CYCLES_PER_SECOND - 1;
+        mbc.set_ram(0xa000, 0x60).unwrap();
+        assert_eq!(mbc.rtc.minutes, 89);
+
+        mbc.tick();
+        mbc.set_ram(0xa000, 0x60).unwrap();
+        assert_eq!(mbc.rtc.minutes, 90);
+    }
+
+    #[test]
+    fn test_huc3_save_keeps_clock_running() {
+        let mut mbc = setup_huc3();
+        mbc.cycles = 60 * 10 * CYCLES_PER_SECOND;
+
+        let save = mbc.save(1000);
+        assert_eq!(save.len(), mbc.ram.len() + 12);
+
+        let mut other = HuC3::default();
+        other.load(save, 1000 + 60 * 60);
+        assert_eq!(read_time(&mut other, 0), (70, 0));
+    }
+}
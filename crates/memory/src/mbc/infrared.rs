@@ -0,0 +1,70 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The other end of an infrared port, the cartridge drives its own LED
+/// and samples the light coming from the peer.
+pub trait Infrared: std::fmt::Debug {
+    fn emit(&mut self, on: bool);
+    fn receive(&self) -> bool;
+}
+
+/// Nothing in front of the sensor, the light is never seen
+#[derive(Debug, Default)]
+pub struct NoPeer;
+
+impl Infrared for NoPeer {
+    fn emit(&mut self, _on: bool) {}
+
+    fn receive(&self) -> bool {
+        false
+    }
+}
+
+/// One side of an in-process link between two emulated cartridges
+#[derive(Debug)]
+pub struct InfraredLink {
+    leds: Rc<RefCell<[bool; 2]>>,
+    side: usize,
+}
+
+impl InfraredLink {
+    pub fn pair() -> (Self, Self) {
+        let leds = Rc::new(RefCell::new([false; 2]));
+        (
+            InfraredLink {
+                leds: leds.clone(),
+                side: 0,
+            },
+            InfraredLink { leds, side: 1 },
+        )
+    }
+}
+
+impl Infrared for InfraredLink {
+    fn emit(&mut self, on: bool) {
+        self.leds.borrow_mut()[self.side] = on;
+    }
+
+    fn receive(&self) -> bool {
+        self.leds.borrow()[1 - self.side]
+    }
+}
+
+#[cfg(test)]
+mod test_infrared {
+    use super::{Infrared, InfraredLink};
+
+    #[test]
+    fn test_link_sees_the_other_side() {
+        let (mut left, mut right) = InfraredLink::pair();
+
+        left.emit(true);
+        assert!(right.receive());
+        assert!(!left.receive());
+
+        right.emit(true);
+        left.emit(false);
+        assert!(left.receive());
+        assert!(!right.receive());
+    }
+}
@@ -1,5 +1,6 @@
 use super::bus::Mbc;
 use super::consts;
+use shared::snapshot::{Reader, Snapshot, Writer};
 use shared::Error;
 use std::convert::AsRef;
 
@@ -43,6 +44,14 @@ impl Mbc for Mbc0 {
     }
 }
 
+impl Snapshot for Mbc0 {
+    fn snapshot(&self, _writer: &mut Writer) {}
+
+    fn restore(&mut self, _reader: &mut Reader) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
 impl Mbc0 {
     pub fn new(data: Vec<u8>) -> Box<Self> {
         Box::new(Self { data })
@@ -1,9 +1,8 @@
 use super::bus::Mbc;
 use crate::{Cartridge, Header};
+use shared::snapshot::{Reader, Snapshot, Writer};
 use shared::Error;
 use std::convert::AsRef;
-use std::io::prelude::*;
-use std::{fs, io, path};
 
 #[derive(Debug)]
 pub struct Mbc1 {
@@ -13,7 +12,8 @@ pub struct Mbc1 {
     ram_mode: bool,
     rombank: usize,
     rambank: usize,
-    savepath: Option<path::PathBuf>,
+    /// The RAM is kept by a battery, see `save::SaveFile`
+    battery: bool,
 }
 
 impl AsRef<Vec<u8>> for Mbc1 {
@@ -22,17 +22,6 @@ impl AsRef<Vec<u8>> for Mbc1 {
     }
 }
 
-impl Drop for Mbc1 {
-    fn drop(&mut self) {
-        match self.savepath {
-            None => {}
-            Some(ref path) => {
-                let _ = fs::File::create(path).and_then(|mut f| f.write_all(&*self.ram));
-            }
-        };
-    }
-}
-
 impl Mbc for Mbc1 {
     fn get_rom(&self, address: usize) -> Result<u8, Error> {
         let index = if address < 0x4000 {
@@ -86,46 +75,53 @@ impl Mbc for Mbc1 {
         self.ram[(rambank * 0x2000) | ((address & 0x1FFF) as usize)] = data;
         Ok(())
     }
+
+    fn battery(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_battery(&mut self, mut data: Vec<u8>) {
+        data.resize(self.ram.len(), 0);
+        self.ram = data;
+    }
+}
+
+impl Snapshot for Mbc1 {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.bytes(&self.ram);
+        writer.bool(self.ram_on);
+        writer.bool(self.ram_mode);
+        writer.usize(self.rombank);
+        writer.usize(self.rambank);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        reader.bytes_into(&mut self.ram)?;
+        self.ram_on = reader.bool()?;
+        self.ram_mode = reader.bool()?;
+        self.rombank = reader.usize()?;
+        self.rambank = reader.usize()?;
+        Ok(())
+    }
 }
 
 impl Mbc1 {
-    pub fn new(header: Header, data: Vec<u8>, file: path::PathBuf) -> Box<Self> {
-        let (svpath, ramsize) = match header.cartridge {
-            Cartridge::Mbc1Ram => (None, header.ram_size.get_size()),
-            Cartridge::Mbc1RamBattery => (
-                Some(file.with_extension("gbsave")),
-                header.ram_size.get_size(),
-            ),
-            _ => (None, 0),
+    pub fn new(header: Header, data: Vec<u8>) -> Box<Self> {
+        let (battery, ramsize) = match header.cartridge {
+            Cartridge::Mbc1Ram => (false, header.ram_size.get_size()),
+            Cartridge::Mbc1RamBattery => (true, header.ram_size.get_size()),
+            _ => (false, 0),
         };
 
-        let mut res = Mbc1 {
+        let res = Mbc1 {
             rom: data,
             ram: ::std::iter::repeat(0u8).take(ramsize).collect(),
             ram_on: false,
             ram_mode: false,
             rombank: 1,
             rambank: 0,
-            savepath: svpath,
+            battery,
         };
-        let _ = res.loadram();
         Box::new(res)
     }
-
-    fn loadram(&mut self) -> Result<(), Error> {
-        match self.savepath {
-            None => Ok(()),
-            Some(ref savepath) => {
-                let mut data = vec![];
-                match fs::File::open(savepath).and_then(|mut f| f.read_to_end(&mut data)) {
-                    Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
-                    Err(_) => Err(Error::FailedRamLoad),
-                    Ok(..) => {
-                        self.ram = data;
-                        Ok(())
-                    }
-                }
-            }
-        }
-    }
 }
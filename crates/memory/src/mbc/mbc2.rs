@@ -1,226 +1,228 @@
 use super::bus::Mbc;
 use super::consts;
+use crate::{Cartridge, Header};
+use shared::snapshot::{Reader, Snapshot, Writer};
 use shared::Error;
 use std::convert::AsRef;
 
 #[derive(Debug)]
 pub struct Mbc2 {
-    ram_lock: bool,
-    data: Vec<u8>,
-    /// Max 16 0x00 ..= 0x0f
-    rom_bank: u8,
+    rom: Vec<u8>,
+    /// 512 x 4 bits, only the lower nibble of each byte is meaningful
+    ram: Vec<u8>,
+    ram_on: bool,
+    /// Max 16 0x01 ..= 0x0f
+    rombank: usize,
+    /// The RAM is kept by a battery, see `save::SaveFile`
+    battery: bool,
 }
 
 impl Default for Mbc2 {
     fn default() -> Self {
         Mbc2 {
-            ram_lock: false,
-            data: vec![0; consts::MBC2_MAX_SIZE],
-            rom_bank: 1,
+            rom: vec![0; consts::MBC2_MAX_SIZE],
+            ram: vec![0; consts::MBC2_RAM_SIZE],
+            ram_on: false,
+            rombank: 1,
+            battery: false,
         }
     }
 }
 
 impl AsRef<Vec<u8>> for Mbc2 {
     fn as_ref(&self) -> &Vec<u8> {
-        self.data.as_ref()
+        self.rom.as_ref()
     }
 }
 
 impl Mbc for Mbc2 {
-    fn get(&self, address: usize) -> Result<u8, Error> {
-        match address {
-            consts::MBC_BANK0_START..=consts::MBC_BANK0_END => Ok(self.data[address]),
-            consts::MBC_BANK1_START..=consts::MBC_BANK1_END => {
-                Ok(self.data[(self.rom_bank as usize * consts::MBC_BANK1_START)
-                    + (address - consts::MBC_BANK1_START)])
-            }
-            consts::MBC_RAM_START..=consts::MBC2_ERAM_END => {
-                if self.ram_lock {
-                    Ok(self.data[address - self.get_ram_offset(address)] & 0xf)
-                } else {
-                    // Should be Undefined behavior or raise an Error
-                    Ok(0)
-                }
-            }
-            _ => unreachable!(),
-        }
+    fn get_rom(&self, address: usize) -> Result<u8, Error> {
+        let index = if address < 0x4000 {
+            address
+        } else {
+            (self.rombank * 0x4000) | (address & 0x3FFF)
+        };
+        Ok(*self.rom.get(index).unwrap_or(&0xFF))
     }
 
-    fn set(&mut self, address: usize, data: u8) -> Result<(), Error> {
+    /// The whole 0000-3FFF area is a register, bit 8 of the address selects which one:
+    /// cleared for the RAM enable, set for the ROM bank number.
+    fn set_rom(&mut self, address: usize, data: u8) -> Result<(), Error> {
         match address {
-            consts::MBC2_REG_START..=consts::MBC2_REG_END => self.mbc2_register(address, data),
-            consts::MBC_RAM_START..=consts::MBC2_ERAM_END => {
-                if self.ram_lock {
-                    let offset = self.get_ram_offset(address);
-                    self.data[address - offset] = data & 0xf
+            0x0000..=0x3FFF if address & consts::MBC2_REGISTER_SELECT == 0 => {
+                self.ram_on = data & 0x0F == 0x0A;
+            }
+            0x0000..=0x3FFF => {
+                self.rombank = match (data as usize) & 0x0F {
+                    0 => 1,
+                    n => n,
                 }
-            } // Else should be undefined behavior Or Err
-            _ => return Err(shared::Error::IllegalSet(address, data)),
+            }
+            _ => {}
         };
         Ok(())
     }
-}
 
-impl Mbc2 {
-    pub fn new(data: Vec<u8>) -> Box<Self> {
-        Box::new(Mbc2 {
-            ram_lock: false,
-            data,
-            rom_bank: 1,
-        })
-    }
-
-    /// If address & 0x100 == 1 : change the rom bank
-    /// else if data == MBC_MAGIC_LOCK (0x0a) enable RAM operation by setting the ram lock as true
-    fn mbc2_register(&mut self, address: usize, data: u8) {
-        if address & consts::MBC2_MAGIC_BYTE != 0 {
-            self.rom_bank = if data == 0 { 1 } else { data & 0xf };
-        } else {
-            self.ram_lock = (data & 0x0f) == consts::MBC_MAGIC_LOCK as u8;
+    /// The built-in RAM only has 9 address lines, so it is echoed across the whole
+    /// A000-BFFF area. The upper nibble is left floating and reads back as ones.
+    fn get_ram(&self, address: usize) -> Result<u8, Error> {
+        if !self.ram_on {
+            return Ok(0xFF);
         }
+        Ok(self.ram[address & (consts::MBC2_RAM_SIZE - 1)] | 0xF0)
     }
 
-    /// Get the RAM or the Echoes RAM Offset
-    fn get_ram_offset(&self, address: usize) -> usize {
-        match address {
-            consts::MBC_RAM_START..=consts::MBC2_RAM_END => consts::MBC_RAM_START,
-            consts::MBC2_ERAM_START..=consts::MBC2_ERAM_END => consts::MBC2_ERAM_START,
-            _ => unreachable!(),
+    fn set_ram(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        if !self.ram_on {
+            return Ok(());
         }
+        self.ram[address & (consts::MBC2_RAM_SIZE - 1)] = data & 0x0F;
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod mbc2_test {
-    use super::{Mbc2, Mbc};
+    fn battery(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
 
-    const FILE: &[u8; 262144] = include_bytes!("../../../../roms/Mystic_Quest.gb");
+    fn load_battery(&mut self, mut data: Vec<u8>) {
+        data.resize(self.ram.len(), 0);
+        self.ram = data;
+    }
+}
 
-    #[test]
-    fn test_is_mbc2_rom() {
-        let rom_file = FILE.to_vec();
-        let mbc = Mbc2::new(rom_file);
-        assert_eq!(mbc.data[0x147], 0x06);
+impl Snapshot for Mbc2 {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.bytes(&self.ram);
+        writer.bool(self.ram_on);
+        writer.usize(self.rombank);
     }
 
-    #[test]
-    fn test_mbc2_get_0() {
-        let rom_file = FILE.to_vec();
-        let mbc = Mbc2::new(rom_file);
-        let data = mbc.get(0);
-        assert_eq!(data.unwrap(), 0xc3);
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        reader.bytes_into(&mut self.ram)?;
+        self.ram_on = reader.bool()?;
+        self.rombank = reader.usize()?;
+        Ok(())
     }
+}
 
-    #[test]
-    fn test_mbc2_get_ram_a0e0() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc2::new(rom_file);
+impl Mbc2 {
+    pub fn new(header: Header, data: Vec<u8>) -> Box<Self> {
+        let battery = matches!(header.cartridge, Cartridge::Mbc2Battery);
+
+        let res = Mbc2 {
+            rom: data,
+            ram: vec![0; consts::MBC2_RAM_SIZE],
+            ram_on: false,
+            rombank: 1,
+            battery,
+        };
+        Box::new(res)
+    }
+}
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x00ff, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+#[cfg(test)]
+mod mbc2_test {
+    use super::{Mbc, Mbc2};
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x0000a0e0, 0xeb).unwrap();
+    /// 16 banks of ROM, each byte holding its bank number
+    fn setup_mbc2() -> Mbc2 {
+        Mbc2 {
+            rom: (0..16u8).flat_map(|bank| vec![bank; 0x4000]).collect(),
+            ..Default::default()
+        }
+    }
 
-        let data = mbc.get(0x0000a0e0);
-        assert_eq!(data.unwrap(), 0x0b);
+    #[test]
+    fn test_mbc2_get_bank_0() {
+        let mbc = setup_mbc2();
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x00ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        assert_eq!(mbc.get_rom(0x0150).unwrap(), 0x00);
+        assert_eq!(mbc.get_rom(0x4000).unwrap(), 0x01);
     }
 
     #[test]
     fn test_mbc2_unlock() {
-        let rom_file = FILE.to_vec(); // MBC2 + RAM + BATTERY
-        let mut mbc = Mbc2::new(rom_file);
+        let mut mbc = setup_mbc2();
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x00ff, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+        mbc.set_rom(0x00ff, 0x0a).unwrap();
+        assert!(mbc.ram_on);
     }
 
     #[test]
     fn test_mbc2_lock() {
-        let rom_file = FILE.to_vec(); // MBC2 + RAM + BATTERY
-        let mut mbc = Mbc2::new(rom_file);
+        let mut mbc = setup_mbc2();
+
+        mbc.set_rom(0x00ff, 0x0a).unwrap();
+        assert!(mbc.ram_on);
+
+        mbc.set_rom(0x00fb, 0x03).unwrap();
+        assert!(!mbc.ram_on)
+    }
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x00ff, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+    #[test]
+    fn test_mbc2_unlock_ignores_bit_8_set() {
+        let mut mbc = setup_mbc2();
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x00fb, 0x03).unwrap();
-        assert!(!mbc.ram_lock)
+        mbc.set_rom(0x0100, 0x0a).unwrap();
+        assert!(!mbc.ram_on);
+        assert_eq!(mbc.rombank, 0x0a);
     }
 
     #[test]
     fn test_mbc2_change_bank_to_0() {
-        let rom_file = FILE.to_vec(); // MBC2 + RAM + BATTERY
-        let mut mbc = Mbc2::new(rom_file);
+        let mut mbc = setup_mbc2();
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x2152, 0x00).unwrap();
-        assert_eq!(mbc.rom_bank, 0x01);
+        mbc.set_rom(0x2152, 0x00).unwrap();
+        assert_eq!(mbc.rombank, 0x01);
     }
 
     #[test]
     fn test_mbc2_change_bank_0a() {
-        let rom_file = FILE.to_vec(); // MBC2 + RAM + BATTERY
-        let mut mbc = Mbc2::new(rom_file);
+        let mut mbc = setup_mbc2();
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x3f52, 0x1a).unwrap();
-        assert_eq!(mbc.rom_bank, 0x0a);
+        mbc.set_rom(0x3f52, 0x1a).unwrap();
+        assert_eq!(mbc.rombank, 0x0a);
+        assert_eq!(mbc.get_rom(0x4242).unwrap(), 0x0a);
     }
 
     #[test]
     fn test_mbc2_change_bank_0f() {
-        let rom_file = FILE.to_vec(); // MBC2 + RAM + BATTERY
-        let mut mbc = Mbc2::new(rom_file);
+        let mut mbc = setup_mbc2();
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x2fff, 0xff).unwrap();
-        assert_eq!(mbc.rom_bank, 0x0f);
+        mbc.set_rom(0x2fff, 0xff).unwrap();
+        assert_eq!(mbc.rombank, 0x0f);
+        assert_eq!(mbc.get_rom(0x7fff).unwrap(), 0x0f);
     }
 
     #[test]
     fn test_mbc2_write_in_ram_a130() {
-        let rom_file = FILE.to_vec(); // MBC2 + RAM + BATTERY
-        let mut mbc = Mbc2::new(rom_file);
-
-        <Mbc2 as Mbc>::set(&mut mbc, 0x00f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
-
-        <Mbc2 as Mbc>::set(&mut mbc, 0x0000a130, 0xca).unwrap();
+        let mut mbc = setup_mbc2();
 
-        let data = mbc.get(0x0000a130);
-        assert_eq!(data.unwrap(), 0x0a);
+        mbc.set_rom(0x00f5, 0x0a).unwrap();
+        mbc.set_ram(0xa130, 0xca).unwrap();
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x00ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        assert_eq!(mbc.get_ram(0xa130).unwrap(), 0xfa);
     }
 
     #[test]
-    fn test_mbc2_read_in_eram_a630() {
-        let rom_file = FILE.to_vec(); // MBC2 + RAM + BATTERY
-        let mut mbc = Mbc2::new(rom_file);
+    fn test_mbc2_ram_is_mirrored() {
+        let mut mbc = setup_mbc2();
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x00f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+        mbc.set_rom(0x00f5, 0x0a).unwrap();
+        mbc.set_ram(0xa0e0, 0x0b).unwrap();
 
-        let data = mbc.get(0x0000a630);
-        assert_eq!(data.unwrap(), 0x0E);
-
-        <Mbc2 as Mbc>::set(&mut mbc, 0x00ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        assert_eq!(mbc.get_ram(0xa2e0).unwrap() & 0x0f, 0x0b);
+        assert_eq!(mbc.get_ram(0xbee0).unwrap() & 0x0f, 0x0b);
     }
 
     #[test]
-    fn test_mbc2_change_bank_13() {
-        let rom_file = FILE.to_vec(); // MBC2 + RAM + BATTERY
-        let mut mbc = Mbc2::new(rom_file);
-
-        <Mbc2 as Mbc>::set(&mut mbc, 0x00f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+    fn test_mbc2_locked_ram() {
+        let mut mbc = setup_mbc2();
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x1f4b, 0x0d).unwrap();
-        assert_eq!(mbc.rom_bank, 13);
+        mbc.set_ram(0xa000, 0x05).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0xff);
 
-        <Mbc2 as Mbc>::set(&mut mbc, 0x00ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        mbc.set_rom(0x0000, 0x0a).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0xf0);
     }
 }
@@ -1,49 +1,54 @@
 use super::bus::Mbc;
 use super::consts;
+use crate::{Cartridge, Header};
+use shared::snapshot::{Reader, Snapshot, Writer};
 use shared::Error;
-use std::convert::AsRef;
+use std::convert::{AsRef, TryInto};
 
-/// Return the epoch in microseconds.
-fn get_epoch() -> u64 {
+/// Return the epoch in seconds.
+pub(super) fn get_epoch() -> u64 {
     let epoch = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .expect("Couldn't get epoch");
-    (epoch.as_micros() as u64) / 1_000_000
+    epoch.as_secs()
 }
 
 #[derive(Debug)]
 pub struct Mbc3 {
-    ram_lock: bool,
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_on: bool,
     latch: bool,
-    data: Vec<u8>,
-    rom_bank: u8,
-    ram_bank: u8,
-    rtc: Mbc3Rtc,
+    /// Max 128 0x01 ..= 0x7f
+    rombank: usize,
+    /// 0x00 ..= 0x03 selects a RAM bank, 0x08 ..= 0x0c a RTC register
+    rambank: usize,
+    rtc: Option<Mbc3Rtc>,
+    /// The RAM is kept by a battery, see `save::SaveFile`
+    battery: bool,
+    /// T-cycles since the cartridge was powered on, the RTC time
+    cycles: u64,
 }
 
 impl Default for Mbc3 {
     fn default() -> Self {
         Mbc3 {
-            ram_lock: false,
+            rom: vec![0; consts::MBC3_MAX_SIZE],
+            ram: vec![0; consts::MBC3_RAM_SIZE],
+            ram_on: false,
             latch: false,
-            data: vec![0; consts::MBC3_MAX_SIZE],
-            rom_bank: 0,
-            ram_bank: 0,
-            rtc: Mbc3Rtc {
-                seconds: 3,
-                minutes: 2,
-                hours: 1,
-                dc_lower: 0,
-                dc_upper: 0,
-                epoch: 0,
-            },
+            rombank: 1,
+            rambank: 0,
+            rtc: Some(Mbc3Rtc::default()),
+            battery: false,
+            cycles: 0,
         }
     }
 }
 
 impl AsRef<Vec<u8>> for Mbc3 {
     fn as_ref(&self) -> &Vec<u8> {
-        self.data.as_ref()
+        self.rom.as_ref()
     }
 }
 
@@ -53,539 +58,531 @@ impl AsRef<Vec<u8>> for Mbc3 {
 /// Hours      0-23    0x0A
 /// DC Lower   0-255   0x0B    The lower 8 bits of the Day Counter
 /// DC Upper           0x0C    bit 0 => 9th bit of the Day Counter, bit 6 => Halt, bit 7 => Day Counter Carry Bit
-#[derive(Debug)]
-struct Mbc3Rtc {
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Registers {
     seconds: u8,
     minutes: u8,
     hours: u8,
     dc_lower: u8,
     dc_upper: u8,
+}
+
+/// The live registers keep counting from the emulated clock, the game only
+/// ever reads the latched copy.
+#[derive(Debug, Default, Clone)]
+struct Mbc3Rtc {
+    live: Registers,
+    latched: Registers,
+    /// Emulated time in seconds at which `live` was last brought up to date,
+    /// host time in the `.sav` footer
     epoch: u64,
 }
 
-impl Mbc for Mbc3 {
-    fn get(&self, address: usize) -> Result<u8, Error> {
-        match address {
-            consts::MBC_BANK0_START..=consts::MBC_BANK0_END => Ok(self.data[address]),
-            consts::MBC_BANK1_START..=consts::MBC_BANK1_END => {
-                Ok(self.data[(self.rom_bank as usize * consts::MBC_BANK1_START)
-                    + (address - consts::MBC_BANK1_START)])
-            }
-            consts::MBC_RAM_START..=consts::MBC_RAM_END => Ok(self.get_ram(address)),
-            _ => unreachable!(),
+impl Registers {
+    fn get(&self, register: usize) -> u8 {
+        match register {
+            0x08 => self.seconds,
+            0x09 => self.minutes,
+            0x0a => self.hours,
+            0x0b => self.dc_lower,
+            0x0c => self.dc_upper,
+            _ => 0xFF,
         }
     }
 
-    fn set(&mut self, address: usize, data: u8) -> Result<(), Error> {
-        match address {
-            consts::MBC3_REG0_START..=consts::MBC3_REG0_END => self.update_ram_lock(data),
-            consts::MBC3_REG1_START..=consts::MBC3_REG1_END => self.update_rom_bank(data),
-            consts::MBC3_REG2_START..=consts::MBC3_REG2_END => self.update_ram_bank(data),
-            consts::MBC3_REG3_START..=consts::MBC3_REG3_END => self.latch_rtc_register(data),
-            consts::MBC3_REG4_START..=consts::MBC3_REG4_END => self.set_ram(address, data),
-            _ => Err(shared::Error::IllegalSet(address, data)),
+    fn set(&mut self, register: usize, data: u8) {
+        match register {
+            0x08 => self.seconds = data & 0x3f,
+            0x09 => self.minutes = data & 0x3f,
+            0x0a => self.hours = data & 0x1f,
+            0x0b => self.dc_lower = data,
+            0x0c => self.dc_upper = data & 0xc1,
+            _ => {}
         }
     }
-}
 
-impl Mbc3 {
-    pub fn new(data: Vec<u8>) -> Box<Self> {
-        Box::new(Mbc3 {
-            ram_lock: false,
-            latch: false,
-            data,
-            rom_bank: 0,
-            ram_bank: 0,
-            rtc: Mbc3Rtc {
-                seconds: 3,
-                minutes: 2,
-                hours: 1,
-                dc_lower: 0,
-                dc_upper: 0,
-                epoch: 0,
-            },
-        })
+    fn halted(&self) -> bool {
+        self.dc_upper & 0x40 != 0
+    }
+
+    /// Add rtc.dc_lower & rtc.dc_upper to retrieve the day
+    fn get_days(&self) -> u64 {
+        ((self.dc_upper as u64 & 1) << 8) | self.dc_lower as u64
+    }
+
+    /// Convert RTC register into a number of seconds
+    fn rtc_to_epoch(&self) -> u64 {
+        let sec = self.seconds as u64;
+        let min = self.minutes as u64;
+        let hours = self.hours as u64;
+        let days = self.get_days();
+        (days * 24 + hours) * 3600 + min * 60 + sec
     }
 
-    /// Retrieve a RAM bank or a RTC value depending on the Ram lock and the RAM bank number
-    fn get_ram(&self, address: usize) -> u8 {
-        if !self.ram_lock {
-            // Should be undefined behavior or raise an Error
-            return 0;
+    /// Convert a number of seconds into RTC, the day counter wraps after 511
+    /// and sets the carry bit, which stays set until the game clears it.
+    fn epoch_to_rtc(&mut self, epoch: u64) {
+        let day = epoch / (3600 * 24);
+        if day > 0x1ff {
+            self.dc_upper |= 0x80;
         }
-        match self.ram_bank {
-            0x00..=0x03 => {
-                self.data[(self.ram_bank as usize * consts::MBC_RAM_BASE)
-                    | (address - consts::MBC_RAM_OFFSET)]
-            }
-            0x08 => self.rtc.seconds,
-            0x09 => self.rtc.minutes,
-            0x0a => self.rtc.hours,
-            0x0b => self.rtc.dc_lower,
-            0x0c => self.rtc.dc_upper,
-            _ => self.data[self.ram_bank as usize - consts::MBC3_RTC_OFFSET],
+        self.seconds = (epoch % 60) as u8;
+        self.minutes = ((epoch / 60) % 60) as u8;
+        self.hours = ((epoch / 3600) % 24) as u8;
+        self.dc_lower = day as u8;
+        self.dc_upper = (self.dc_upper & !1) | ((day >> 8) & 1) as u8;
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.dc_lower,
+            self.dc_upper,
+        ]
+        .iter()
+        .flat_map(|register| (*register as u32).to_le_bytes())
+        .collect()
+    }
+
+    fn from_bytes(data: &[u8]) -> Self {
+        let register = |index: usize| data[index * 4];
+        Registers {
+            seconds: register(0),
+            minutes: register(1),
+            hours: register(2),
+            dc_lower: register(3),
+            dc_upper: register(4),
         }
     }
+}
 
-    /// Latch / Save the current time since the Epoch in Mbc3.rtc.epoch
-    fn latch(&mut self) {
-        let new_epoch = if self.rtc.dc_upper & 0x40 == 0 {
-            get_epoch()
-        } else {
-            self.rtc.epoch
-        };
-        let elapsed = new_epoch - self.rtc.epoch;
+impl Mbc3Rtc {
+    /// Bring the live registers up to `now`, unless the clock is halted
+    fn update(&mut self, now: u64) {
+        if !self.live.halted() {
+            let elapsed = now.saturating_sub(self.epoch);
+            let seconds = self.live.rtc_to_epoch() + elapsed;
+            self.live.epoch_to_rtc(seconds);
+        }
+        self.epoch = now;
+    }
 
-        let last_day = self.get_days();
-        let last_secs = self.rtc_to_epoch();
-        self.epoch_to_rtc(last_secs + elapsed);
-        let new_day = self.get_days();
+    fn latch(&mut self, now: u64) {
+        self.update(now);
+        self.latched = self.live;
+    }
 
-        // Overflow
-        if new_day < last_day {
-            self.rtc.dc_upper |= 0x80;
-        }
+    fn set(&mut self, register: usize, data: u8, now: u64) {
+        self.update(now);
+        self.live.set(register, data);
+        self.latched.set(register, data);
+    }
 
-        dbg!(
-            "Latching RTC: {:04}/{:02}:{:02}:{:02}",
-            self.get_days(),
-            self.rtc.hours,
-            self.rtc.minutes,
-            self.rtc.seconds
-        );
+    /// Footer appended after the battery RAM, in the layout most emulators share:
+    /// live registers, latched registers (5 x u32 LE each) then a u64 LE timestamp.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut footer = self.live.to_bytes();
+        footer.extend(self.latched.to_bytes());
+        footer.extend(self.epoch.to_le_bytes());
+        footer
+    }
 
-        self.rtc.epoch = new_epoch;
+    /// Some emulators only store a 32 bits timestamp, accept both.
+    fn from_bytes(data: &[u8]) -> Option<Self> {
+        let epoch = match data.len() {
+            consts::MBC3_RTC_FOOTER => u64::from_le_bytes(data[40..48].try_into().ok()?),
+            44 => u32::from_le_bytes(data[40..44].try_into().ok()?) as u64,
+            _ => return None,
+        };
+        Some(Mbc3Rtc {
+            live: Registers::from_bytes(&data[0..20]),
+            latched: Registers::from_bytes(&data[20..40]),
+            epoch,
+        })
     }
+}
 
-    /// Register to latch the RTC values into the RAM, should write 0x0001 in this register address area
-    fn latch_rtc_register(&mut self, data: u8) -> Result<(), Error> {
-        if self.latch {
-            if data == 0x1 {
-                self.latch();
-                // This is commentend, ask Guillaume
-                //std::time::Duration::from_millis(4);
-            }
-            self.latch = false;
-        } else if data == 0 {
-            self.latch = true;
-        }
-        Ok(())
+impl Mbc for Mbc3 {
+    fn get_rom(&self, address: usize) -> Result<u8, Error> {
+        let index = if address < 0x4000 {
+            address
+        } else {
+            (self.rombank * 0x4000) | (address & 0x3FFF)
+        };
+        Ok(*self.rom.get(index).unwrap_or(&0xFF))
     }
 
-    /// Write into the RAM if the Ram bank number if <= 0x03
-    /// Or update RTC register if 0x08 <= ram_bank 0x0c
-    fn set_ram(&mut self, address: usize, data: u8) -> Result<(), Error> {
-        if !self.ram_lock {
-            return Err(shared::Error::RamLock(address));
-        }
-        if self.ram_bank <= 0x03 {
-            self.data[(self.ram_bank as usize * consts::MBC_RAM_BASE)
-                | (address - consts::MBC_RAM_OFFSET)] = data;
-            return Ok(());
-        }
-        match self.ram_bank {
-            0x08 => {
-                self.rtc.seconds = data;
-            }
-            0x09 => {
-                self.rtc.minutes = data;
-            }
-            0x0a => {
-                self.rtc.hours = data;
+    fn set_rom(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_on = data & 0x0F == 0x0A;
             }
-            0x0b => {
-                self.rtc.dc_lower = data;
+            0x2000..=0x3FFF => {
+                self.rombank = match (data as usize) & 0x7F {
+                    0 => 1,
+                    n => n,
+                }
             }
-            0x0c => {
-                self.rtc.dc_upper = data;
+            0x4000..=0x5FFF => {
+                self.rambank = (data as usize) & 0x0F;
             }
-            _ => return Err(shared::Error::IllegalSet(address, data)), // Should be undefined behavior or Ok(self.data[self.ram_bank - consts::MBC3_RTC_OFFSET] = data),
-        }
-        self.rtc.epoch = get_epoch();
+            0x6000..=0x7FFF => self.latch_rtc_register(data, self.now()),
+            _ => return Err(Error::IllegalSet(address, data)),
+        };
         Ok(())
     }
 
-    /// Update RAM bank number
-    fn update_ram_bank(&mut self, data: u8) -> Result<(), Error> {
-        self.ram_bank = data & 0x0f;
-        Ok(())
+    /// Retrieve a RAM bank or a latched RTC value depending on the RAM bank number
+    fn get_ram(&self, address: usize) -> Result<u8, Error> {
+        if !self.ram_on {
+            return Ok(0xFF);
+        }
+        Ok(match (self.rambank, &self.rtc) {
+            (0x00..=0x03, _) => *self
+                .ram
+                .get((self.rambank * 0x2000) | (address & 0x1FFF))
+                .unwrap_or(&0xFF),
+            (0x08..=0x0c, Some(rtc)) => rtc.latched.get(self.rambank),
+            _ => 0xFF,
+        })
     }
 
-    /// Update ROM bank number $00 bank is not valid
-    fn update_rom_bank(&mut self, data: u8) -> Result<(), Error> {
-        self.rom_bank = match data & 0x7f {
-            0 => 1,
-            _nbr => _nbr,
-        };
+    /// Write into the RAM if the RAM bank number is <= 0x03
+    /// Or update RTC register if 0x08 <= ram_bank 0x0c
+    fn set_ram(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        self.set_ram_at(address, data, self.now());
         Ok(())
     }
 
-    /// Enable RAM Read/Write Operation by sending 0x0a into reg0
-    fn update_ram_lock(&mut self, data: u8) -> Result<(), Error> {
-        self.ram_lock = data == consts::MBC_MAGIC_LOCK;
-        Ok(())
+    fn tick(&mut self) {
+        self.cycles += 1;
     }
 
-    /// Add rtc.dc_lower & rtc.dc_upper to retrieve the day
-    fn get_days(&mut self) -> u64 {
-        ((self.rtc.dc_upper as u64 & 1) << 8) & self.rtc.dc_lower as u64
+    fn battery(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.save(get_epoch()))
     }
 
-    /// Convert RTC register into the number of seconds since the Epoch
-    fn rtc_to_epoch(&mut self) -> u64 {
-        let sec = self.rtc.seconds as u64;
-        let min = self.rtc.minutes as u64;
-        let hours = self.rtc.hours as u64;
-        let days = self.get_days();
-        (days * 24 + hours) * 3600 + min * 60 + sec
+    /// The clock catches up with the host time spent since the save
+    fn load_battery(&mut self, data: Vec<u8>) {
+        self.load(data, get_epoch())
     }
+}
 
-    /// Convert a number of seconds since the Epoch into RTC
-    fn epoch_to_rtc(&mut self, epoch: u64) {
-        self.rtc.seconds = (epoch % 60) as u8;
-        self.rtc.minutes = ((epoch / 60) % 60) as u8;
-        self.rtc.hours = ((epoch / 3600) % 24) as u8;
-        let day = epoch / (3600 * 24);
-        self.rtc.dc_lower = day as u8;
-        self.rtc.dc_upper = (self.rtc.dc_upper & !1) | ((day >> 8) & 1) as u8;
+impl Snapshot for Mbc3 {
+    /// The RTC is kept in its `.sav` footer layout, stamped with the emulated time
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.u64(self.cycles);
+        writer.bytes(&self.save(self.now()));
+        writer.bool(self.ram_on);
+        writer.bool(self.latch);
+        writer.usize(self.rombank);
+        writer.usize(self.rambank);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.cycles = reader.u64()?;
+        let data = reader.bytes()?;
+        if data.len() != self.save(0).len() {
+            return Err(Error::InvalidState);
+        }
+        self.load(data, self.now());
+        self.ram_on = reader.bool()?;
+        self.latch = reader.bool()?;
+        self.rombank = reader.usize()?;
+        self.rambank = reader.usize()?;
+        Ok(())
     }
 }
 
-#[cfg(test)]
-mod mbc3_test {
-    use super::{Mbc3, Mbc};
-
-    const FILE: &[u8; 2097152] = include_bytes!("../../../../roms/Pokemon - Version Argent.gbc");
+impl Mbc3 {
+    pub fn new(header: Header, data: Vec<u8>) -> Box<Self> {
+        let ramsize = header.ram_size.get_size();
+        let battery = matches!(
+            header.cartridge,
+            Cartridge::Mbc3TimerBattery
+                | Cartridge::Mbc3TimerRamBattery2
+                | Cartridge::Mbc3RamBattery2
+        );
+        let rtc = match header.cartridge {
+            Cartridge::Mbc3TimerBattery | Cartridge::Mbc3TimerRamBattery2 => {
+                Some(Mbc3Rtc::default())
+            }
+            _ => None,
+        };
 
-    #[test]
-    fn test_mbc3_get_0x0() {
-        let rom_file = FILE.to_vec();
-        let mbc = Mbc3::new(rom_file);
-        let data = mbc.get(0x00000040);
-        assert_eq!(data.unwrap(), 0xc3);
+        let res = Mbc3 {
+            rom: data,
+            ram: vec![0; ramsize],
+            ram_on: false,
+            latch: false,
+            rombank: 1,
+            rambank: 0,
+            rtc,
+            battery,
+            cycles: 0,
+        };
+        Box::new(res)
     }
 
-    #[test]
-    fn test_mbc3_set_lock() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        assert_eq!(mbc.data[0x147], 0x10);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
-
-        mbc.set(0x034b, 0x03).unwrap();
-        assert!(!mbc.ram_lock)
+    /// Seconds of emulated time, the clock the RTC counts from
+    fn now(&self) -> u64 {
+        self.cycles / consts::CYCLES_PER_SECOND
     }
 
-    #[test]
-    fn test_mbc3_reg1_0() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x2156, 0x00).unwrap();
-        assert_eq!(mbc.rom_bank, 0x01);
+    /// Battery RAM followed by the RTC footer when the cartridge has a timer,
+    /// the clock brought up to date and stamped with `epoch`
+    fn save(&self, epoch: u64) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        if let Some(mut rtc) = self.rtc.clone() {
+            rtc.update(self.now());
+            rtc.epoch = epoch;
+            data.extend(rtc.to_bytes());
+        }
+        data
+    }
+
+    /// The saved clock runs until `epoch`, the time its footer is stamped in,
+    /// then goes on from the emulated time
+    fn load(&mut self, mut data: Vec<u8>, epoch: u64) {
+        let ramsize = self.ram.len();
+        let now = self.now();
+        if let Some(ref mut rtc) = self.rtc {
+            if let Some(mut saved) = data.get(ramsize..).and_then(Mbc3Rtc::from_bytes) {
+                saved.update(epoch);
+                saved.epoch = now;
+                *rtc = saved;
+            }
+        }
+        data.resize(ramsize, 0);
+        self.ram = data;
     }
 
-    #[test]
-    fn test_mbc3_reg1_1a() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x2156, 0x1a).unwrap();
-        assert_eq!(mbc.rom_bank, 0x1a);
+    /// Register to latch the RTC values into the RAM, should write 0x00 then 0x01 in this register address area
+    fn latch_rtc_register(&mut self, data: u8, now: u64) {
+        if self.latch && data == 0x01 {
+            if let Some(ref mut rtc) = self.rtc {
+                rtc.latch(now);
+            }
+        }
+        self.latch = data == 0x00;
     }
 
-    #[test]
-    fn test_mbc3_reg1_14() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x2fff, 0x14).unwrap();
-        assert_eq!(mbc.rom_bank, 0x14);
+    fn set_ram_at(&mut self, address: usize, data: u8, now: u64) {
+        if !self.ram_on {
+            return;
+        }
+        match (self.rambank, &mut self.rtc) {
+            (0x00..=0x03, _) => {
+                if let Some(byte) = self
+                    .ram
+                    .get_mut((self.rambank * 0x2000) | (address & 0x1FFF))
+                {
+                    *byte = data;
+                }
+            }
+            (0x08..=0x0c, Some(rtc)) => rtc.set(self.rambank, data, now),
+            _ => {}
+        }
     }
+}
 
-    #[test]
-    fn test_mbc3_get_last_rom_bank() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
+#[cfg(test)]
+mod mbc3_test {
+    use super::consts::CYCLES_PER_SECOND;
+    use super::{Mbc, Mbc3};
+
+    const DAY: u64 = 24 * 3600;
 
-        mbc.set(0x2fff, 0x1f).unwrap();
-        assert_eq!(mbc.rom_bank, 0x1f);
+    /// 128 banks of ROM, each byte holding its bank number, the clock started at 0
+    fn setup_mbc3() -> Mbc3 {
+        let mut mbc = Mbc3 {
+            rom: (0..128u8).flat_map(|bank| vec![bank; 0x4000]).collect(),
+            ..Default::default()
+        };
+        mbc.set_rom(0x01f5, 0x0a).unwrap();
+        mbc
     }
 
-    #[test]
-    fn test_mbc3_change_rom_bank() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
+    fn latch(mbc: &mut Mbc3, now: u64) {
+        mbc.latch_rtc_register(0x00, now);
+        mbc.latch_rtc_register(0x01, now);
+    }
 
-        mbc.set(0x3564, 0x11).unwrap();
-        assert_eq!(mbc.rom_bank, 0x11);
+    fn read_rtc(mbc: &mut Mbc3, register: u8) -> u8 {
+        mbc.set_rom(0x4000, register).unwrap();
+        mbc.get_ram(0xa000).unwrap()
     }
 
     #[test]
-    fn test_mbc3_write_in_ram() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
-
-        mbc.set(0x4f4f, 0x00).unwrap();
-        assert_eq!(mbc.ram_bank, 0);
-
-        mbc.set(0x0000a010, 0xca).unwrap();
-
-        let data = mbc.get(0x0000a010);
-        assert_eq!(data.unwrap(), 0xca);
+    fn test_mbc3_set_lock() {
+        let mut mbc = setup_mbc3();
+        assert!(mbc.ram_on);
 
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        mbc.set_rom(0x034b, 0x03).unwrap();
+        assert!(!mbc.ram_on)
     }
 
     #[test]
-    fn test_mbc3_change_ram_bank() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
-
-        mbc.set(0x4f4f, 0x01).unwrap();
-        assert_eq!(mbc.ram_bank, 1);
+    fn test_mbc3_reg1_0() {
+        let mut mbc = setup_mbc3();
 
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        mbc.set_rom(0x2156, 0x00).unwrap();
+        assert_eq!(mbc.rombank, 0x01);
     }
 
     #[test]
-    fn test_mbc3_set_seconds() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
-
-        mbc.set(0x4f4f, 0x08).unwrap();
-        assert_eq!(mbc.ram_bank, 8);
-
-        mbc.set(0x0aff, 0x28).unwrap();
-
-        let data = mbc.get(0x0aff);
-        assert_eq!(data.unwrap(), 0xc9);
+    fn test_mbc3_change_rom_bank() {
+        let mut mbc = setup_mbc3();
 
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        mbc.set_rom(0x3564, 0x7f).unwrap();
+        assert_eq!(mbc.get_rom(0x4000).unwrap(), 0x7f);
+        assert_eq!(mbc.get_rom(0x0000).unwrap(), 0x00);
     }
 
     #[test]
-    fn test_mbc3_set_minutes() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
-
-        mbc.set(0x4f4f, 0x09).unwrap();
-        assert_eq!(mbc.ram_bank, 9);
-
-        mbc.set(0x0aff, 0x29).unwrap();
+    fn test_mbc3_change_ram_bank() {
+        let mut mbc = setup_mbc3();
 
-        let data = mbc.get(0x0aff);
-        assert_eq!(data.unwrap(), 0xc9);
+        mbc.set_rom(0x4f4f, 0x00).unwrap();
+        mbc.set_ram(0xa010, 0xca).unwrap();
+        mbc.set_rom(0x4f4f, 0x03).unwrap();
+        mbc.set_ram(0xa010, 0xfe).unwrap();
 
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        assert_eq!(mbc.get_ram(0xa010).unwrap(), 0xfe);
+        mbc.set_rom(0x4f4f, 0x00).unwrap();
+        assert_eq!(mbc.get_ram(0xa010).unwrap(), 0xca);
     }
 
     #[test]
-    fn test_mbc3_set_hours() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
-
-        mbc.set(0x4f4f, 0x0a).unwrap();
-        assert_eq!(mbc.ram_bank, 10);
+    fn test_mbc3_rtc_reads_latched_value() {
+        let mut mbc = setup_mbc3();
 
-        mbc.set(0x0aff, 0x30).unwrap();
+        latch(&mut mbc, 0);
+        mbc.rtc.as_mut().unwrap().update(125);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 0);
 
-        let data = mbc.get(0x0aff);
-        assert_eq!(data.unwrap(), 0xc9);
-
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        latch(&mut mbc, 125);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 5);
+        assert_eq!(read_rtc(&mut mbc, 0x09), 2);
     }
 
     #[test]
-    fn test_mbc3_set_dc_lower() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
-
-        mbc.set(0x4f4f, 0x0b).unwrap();
-        assert_eq!(mbc.ram_bank, 11);
+    fn test_mbc3_latch_needs_0_then_1() {
+        let mut mbc = setup_mbc3();
 
-        mbc.set(0xb010, 0x31).unwrap();
+        mbc.latch_rtc_register(0x01, 42);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 0);
 
-        let data = mbc.get(0xb010);
-        assert_eq!(data.unwrap(), 0x31);
-
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        mbc.latch_rtc_register(0x00, 42);
+        mbc.latch_rtc_register(0x00, 42);
+        mbc.latch_rtc_register(0x01, 42);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 42);
     }
 
     #[test]
-    fn test_mbc3_set_dc_upper() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+    fn test_mbc3_rtc_set_registers() {
+        let mut mbc = setup_mbc3();
 
-        mbc.set(0x4f4f, 0x0c).unwrap();
-        assert_eq!(mbc.ram_bank, 12);
+        mbc.set_rom(0x4000, 0x0a).unwrap();
+        mbc.set_ram_at(0xa000, 23, 0);
+        mbc.set_rom(0x4000, 0x09).unwrap();
+        mbc.set_ram_at(0xa000, 59, 0);
+        mbc.set_rom(0x4000, 0x08).unwrap();
+        mbc.set_ram_at(0xa000, 59, 0);
 
-        mbc.set(0xb210, 0x0a).unwrap();
-
-        let data = mbc.get(0xb210);
-        assert_eq!(data.unwrap(), 0x0a);
-
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        latch(&mut mbc, 1);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 0);
+        assert_eq!(read_rtc(&mut mbc, 0x09), 0);
+        assert_eq!(read_rtc(&mut mbc, 0x0a), 0);
+        assert_eq!(read_rtc(&mut mbc, 0x0b), 1);
     }
 
     #[test]
-    fn test_mbc3_get_dc_upper() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
+    fn test_mbc3_rtc_halt() {
+        let mut mbc = setup_mbc3();
 
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+        mbc.set_rom(0x4000, 0x0c).unwrap();
+        mbc.set_ram_at(0xa000, 0x40, 10);
 
-        mbc.set(0x4f4f, 0x0c).unwrap();
-        assert_eq!(mbc.ram_bank, 12);
+        latch(&mut mbc, 1000);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 10);
 
-        let data = mbc.get(0xb040);
-        assert_eq!(data.unwrap(), 0x00);
-
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        mbc.set_rom(0x4000, 0x0c).unwrap();
+        mbc.set_ram_at(0xa000, 0x00, 2000);
+        latch(&mut mbc, 2003);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 13);
     }
 
     #[test]
-    fn test_mbc3_get_seconds() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+    fn test_mbc3_rtc_day_counter() {
+        let mut mbc = setup_mbc3();
 
-        mbc.set(0x4f4f, 0x08).unwrap();
-        assert_eq!(mbc.ram_bank, 8);
-
-        let data = mbc.get(0x0aff);
-        assert_eq!(data.unwrap(), 0xc9);
-
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        latch(&mut mbc, 300 * DAY);
+        assert_eq!(read_rtc(&mut mbc, 0x0b), (300 & 0xff) as u8);
+        assert_eq!(read_rtc(&mut mbc, 0x0c), 0x01);
     }
 
     #[test]
-    fn test_mbc3_get_minutes() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
+    fn test_mbc3_rtc_day_carry() {
+        let mut mbc = setup_mbc3();
 
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+        latch(&mut mbc, 513 * DAY);
+        assert_eq!(read_rtc(&mut mbc, 0x0b), 1);
+        assert_eq!(read_rtc(&mut mbc, 0x0c), 0x80);
 
-        mbc.set(0x4f4f, 0x09).unwrap();
-        assert_eq!(mbc.ram_bank, 9);
+        latch(&mut mbc, 514 * DAY);
+        assert_eq!(read_rtc(&mut mbc, 0x0c), 0x80);
 
-        let data = mbc.get(0xb080);
-        assert_eq!(data.unwrap(), 0x02);
-
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        mbc.set_rom(0x4000, 0x0c).unwrap();
+        mbc.set_ram_at(0xa000, 0x00, 514 * DAY);
+        latch(&mut mbc, 514 * DAY);
+        assert_eq!(read_rtc(&mut mbc, 0x0c), 0x00);
     }
 
     #[test]
-    fn test_mbc3_get_hours() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+    fn test_mbc3_rtc_counts_emulated_time() {
+        let mut mbc = setup_mbc3();
 
-        mbc.set(0x4f4f, 0x0a).unwrap();
-        assert_eq!(mbc.ram_bank, 10);
+        mbc.cycles = 2 * CYCLES_PER_SECOND - 1;
+        mbc.set_rom(0x6000, 0x00).unwrap();
+        mbc.set_rom(0x6000, 0x01).unwrap();
+        assert_eq!(read_rtc(&mut mbc, 0x08), 1);
 
-        let data = mbc.get(0xbf00);
-        assert_eq!(data.unwrap(), 0x01);
-
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        mbc.tick();
+        mbc.set_rom(0x6000, 0x00).unwrap();
+        mbc.set_rom(0x6000, 0x01).unwrap();
+        assert_eq!(read_rtc(&mut mbc, 0x08), 2);
     }
 
     #[test]
-    fn test_mbc3_get_dc_lower() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+    fn test_mbc3_save_keeps_clock_running() {
+        let mut mbc = setup_mbc3();
 
-        mbc.set(0x4f4f, 0x0b).unwrap();
-        assert_eq!(mbc.ram_bank, 11);
+        mbc.set_rom(0x4000, 0x00).unwrap();
+        mbc.set_ram(0xa000, 0x42).unwrap();
+        mbc.set_rom(0x4000, 0x09).unwrap();
+        mbc.set_ram(0xa000, 10).unwrap();
+        let save = mbc.save(1000);
+        assert_eq!(save.len(), mbc.ram.len() + 48);
 
-        let data = mbc.get(0xb0f0);
-        assert_eq!(data.unwrap(), 0);
-
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        let mut other = setup_mbc3();
+        other.load(save, 1000 + 3600);
+        latch(&mut other, 0);
+        assert_eq!(read_rtc(&mut other, 0x09), 10);
+        assert_eq!(read_rtc(&mut other, 0x0a), 1);
+        other.set_rom(0x4000, 0x00).unwrap();
+        assert_eq!(other.get_ram(0xa000).unwrap(), 0x42);
     }
 
     #[test]
-    fn test_mbc3_latch_rtc() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc3::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
-
-        mbc.set(0x6000, 0x00).unwrap();
-        assert!(mbc.latch);
-
-        mbc.set(0x6000, 0x01).unwrap();
-        assert!(!mbc.latch);
-
-        mbc.set(0x4f4f, 0x08).unwrap();
-        assert_eq!(mbc.ram_bank, 8); //seconds
-        let seconds = mbc.get(0xb080);
-        assert_eq!(mbc.rtc.seconds, seconds.unwrap());
-
-        mbc.set(0x4f4f, 0x09).unwrap();
-        assert_eq!(mbc.ram_bank, 9); //minutes
-        let minutes = mbc.get(0xb080);
-        assert_eq!(mbc.rtc.minutes, minutes.unwrap());
-
-        mbc.set(0x4f4f, 0x0a).unwrap();
-        assert_eq!(mbc.ram_bank, 10);
-        let hours = mbc.get(0xbf00); //hours
-        assert_eq!(mbc.rtc.hours, hours.unwrap());
-
-        mbc.set(0x4f4f, 0x0b).unwrap();
-        assert_eq!(mbc.ram_bank, 11);
-        let dc_lower = mbc.get(0xb0f0); //dc_lower
-        assert_eq!(mbc.rtc.dc_lower, dc_lower.unwrap());
-
-        mbc.set(0x4f4f, 0x0c).unwrap();
-        assert_eq!(mbc.ram_bank, 12);
-        let dc_upper = mbc.get(0xb040); //dc_upper
-        assert_eq!(mbc.rtc.dc_upper, dc_upper.unwrap());
+    fn test_mbc3_no_timer() {
+        let mut mbc = setup_mbc3();
+        mbc.rtc = None;
+
+        latch(&mut mbc, 100);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 0xff);
+        assert_eq!(mbc.save(0).len(), mbc.ram.len());
     }
 }
@@ -1,248 +1,277 @@
 use super::bus::Mbc;
 use super::consts;
+use crate::{Cartridge, Header};
+use shared::snapshot::{Reader, Snapshot, Writer};
 use shared::Error;
 use std::convert::AsRef;
 
 #[derive(Debug)]
 pub struct Mbc5 {
-    ram_lock: bool,
-    data: Vec<u8>,
-    rom_bank: u16,
-    ram_bank: u8,
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_on: bool,
+    /// Max 512 0x000 ..= 0x1ff, bank 0 can be mapped in the upper window
+    rombank: usize,
+    /// Max 16 0x00 ..= 0x0f, only 8 on rumble cartridges
+    rambank: usize,
+    /// Rumble cartridges drive the motor with bit 3 of the RAM bank register
+    rumble: Option<bool>,
+    /// The RAM is kept by a battery, see `save::SaveFile`
+    battery: bool,
 }
 
 impl Default for Mbc5 {
     fn default() -> Self {
         Mbc5 {
-            ram_lock: false,
-            data: vec![0; consts::MBC5_MAX_SIZE],
-            rom_bank: 0,
-            ram_bank: 0,
+            rom: vec![0; consts::MBC5_MAX_SIZE],
+            ram: vec![0; consts::MBC5_RAM_SIZE],
+            ram_on: false,
+            rombank: 1,
+            rambank: 0,
+            rumble: None,
+            battery: false,
         }
     }
 }
 
 impl AsRef<Vec<u8>> for Mbc5 {
     fn as_ref(&self) -> &Vec<u8> {
-        self.data.as_ref()
+        self.rom.as_ref()
     }
 }
 
 impl Mbc for Mbc5 {
-    fn get(&self, address: usize) -> Result<u8, Error> {
-        match address {
-            consts::MBC_BANK0_START..=consts::MBC_BANK0_END => Ok(self.data[address]),
-            consts::MBC_BANK1_START..=consts::MBC_BANK1_END => Ok(self.swap_bank_nbr(address)),
-            consts::MBC_RAM_START..=consts::MBC_RAM_END => Ok(self.swap_bank_nbr(address)),
-            _ => unreachable!(),
-        }
+    fn get_rom(&self, address: usize) -> Result<u8, Error> {
+        let index = if address < 0x4000 {
+            address
+        } else {
+            (self.rombank * 0x4000) | (address & 0x3FFF)
+        };
+        Ok(*self.rom.get(index).unwrap_or(&0xFF))
     }
 
-    fn set(&mut self, address: usize, data: u8) -> Result<(), Error> {
+    /// To select the ROM bank number, there are 2 areas to write into
+    /// since its value is 9-bits wide (max value is 0x1FF)
+    fn set_rom(&mut self, address: usize, data: u8) -> Result<(), Error> {
         match address {
-            consts::MBC5_REG0_START..=consts::MBC5_REG0_END => self.update_ram_lock(data),
-            consts::MBC5_REG1_START..=consts::MBC5_REG3_END => self.set_bank_number(address, data),
-            consts::MBC_RAM_START..=consts::MBC_RAM_END => self.set_ram(address, data),
-            _ => Err(shared::Error::IllegalSet(address, data)),
-        }
+            0x0000..=0x1FFF => {
+                self.ram_on = data & 0x0F == 0x0A;
+            }
+            0x2000..=0x2FFF => {
+                self.rombank = (self.rombank & 0x100) | data as usize;
+            }
+            0x3000..=0x3FFF => {
+                self.rombank = (self.rombank & 0x0FF) | (((data as usize) & 0x01) << 8);
+            }
+            0x4000..=0x5FFF => match self.rumble {
+                Some(ref mut motor) => {
+                    *motor = data & 0x08 != 0;
+                    self.rambank = (data as usize) & 0x07;
+                }
+                None => self.rambank = (data as usize) & 0x0F,
+            },
+            0x6000..=0x7FFF => {}
+            _ => return Err(Error::IllegalSet(address, data)),
+        };
+        Ok(())
     }
-}
 
-impl Mbc5 {
-    pub fn new(data: Vec<u8>) -> Box<Self> {
-        Box::new(Mbc5 {
-            ram_lock: false,
-            data,
-            rom_bank: 0,
-            ram_bank: 0,
-        })
+    fn get_ram(&self, address: usize) -> Result<u8, Error> {
+        if !self.ram_on {
+            return Ok(0xFF);
+        }
+        Ok(*self
+            .ram
+            .get((self.rambank * 0x2000) | (address & 0x1FFF))
+            .unwrap_or(&0xFF))
     }
 
-    /// Write the Data into the Ram at the address
     fn set_ram(&mut self, address: usize, data: u8) -> Result<(), Error> {
-        if !self.ram_lock {
-            return Err(shared::Error::RamLock(address));
+        if !self.ram_on {
+            return Ok(());
+        }
+        if let Some(byte) = self
+            .ram
+            .get_mut((self.rambank * 0x2000) | (address & 0x1FFF))
+        {
+            *byte = data;
         }
-        let index =
-            (self.ram_bank as usize * consts::MBC_RAM_BASE) | (address & consts::MBC_RAM_OFFSET);
-        self.data[index] = data;
         Ok(())
     }
 
-    /// Get the Data at the Address depending on the actual bank number
-    fn swap_bank_nbr(&self, address: usize) -> u8 {
-        let (bank_nbr, start_off, end_off) = match address {
-            consts::MBC_BANK1_START..=consts::MBC_BANK1_END => (
-                self.rom_bank,
-                consts::MBC_BANK1_START as u16,
-                consts::MBC_BANK1_START,
-            ),
-            consts::MBC_RAM_START..=consts::MBC_RAM_END => {
-                let bank_nbr = if self.ram_lock {
-                    self.ram_bank as usize
-                } else {
-                    // should ret error undefined behavior
-                    0
-                };
-                (
-                    bank_nbr as u16,
-                    consts::MBC_RAM_BASE as u16,
-                    consts::MBC_RAM_START,
-                )
-            }
-            _ => unreachable!(),
-        };
-        let index =
-            ((bank_nbr * start_off) + (address - end_off) as u16) & (self.data.len() - 1) as u16;
-        self.data[index as usize]
+    fn rumble(&self) -> bool {
+        self.rumble.unwrap_or(false)
     }
 
-    /// To selected the Rom bank number, you've 2 area were to write
-    /// since his value can be contain in a 9-bits wide number (max value is 0x1FF)
-    /// Ram bank number goes from 0x0 to 0xf
-    fn set_bank_number(&mut self, address: usize, data: u8) -> Result<(), Error> {
-        match address {
-            consts::MBC5_REG1_START..=consts::MBC5_REG1_END => {
-                self.rom_bank = (self.rom_bank & 0x100) | (data as u16)
-            }
-            consts::MBC5_REG2_START..=consts::MBC5_REG2_END => {
-                self.rom_bank = (self.rom_bank & 0x0FF) | (((data as u16) & 0x1) << 8)
-            }
-            consts::MBC5_REG3_START..=consts::MBC5_REG3_END => self.ram_bank = data & 0x0f,
-            _ => unreachable!(),
-        }
-        Ok(())
+    fn battery(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
     }
 
-    /// enable RAM Read/Write Operations REG0
-    fn update_ram_lock(&mut self, data: u8) -> Result<(), Error> {
-        self.ram_lock = data == consts::MBC_MAGIC_LOCK;
-        Ok(())
+    fn load_battery(&mut self, mut data: Vec<u8>) {
+        data.resize(self.ram.len(), 0);
+        self.ram = data;
     }
 }
 
-#[cfg(test)]
-mod mbc5_test {
-    use super::{Mbc5, Mbc};
-
-    const FILE: &[u8; 1048576] = include_bytes!("../../../../roms/Pokemon_Rouge.gb");
-
-    #[test]
-    fn test_mbc5_get() {
-        let rom_file = FILE.to_vec();
-        let mbc = Mbc5::new(rom_file);
-        let data = mbc.get(0x00000250);
-        assert_eq!(data.unwrap(), 0xad);
+impl Snapshot for Mbc5 {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.bytes(&self.ram);
+        writer.bool(self.ram_on);
+        writer.usize(self.rombank);
+        writer.usize(self.rambank);
+        writer.bool(self.rumble.unwrap_or(false));
     }
 
-    #[test]
-    fn test_mbc5_set_lock() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc5::new(rom_file);
-
-        assert_eq!(mbc.data[0x147], 0x1b);
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        reader.bytes_into(&mut self.ram)?;
+        self.ram_on = reader.bool()?;
+        self.rombank = reader.usize()?;
+        self.rambank = reader.usize()?;
+        let rumble = reader.bool()?;
+        if let Some(ref mut motor) = self.rumble {
+            *motor = rumble;
+        }
+        Ok(())
+    }
+}
 
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+impl Mbc5 {
+    pub fn new(header: Header, data: Vec<u8>) -> Box<Self> {
+        let (battery, ramsize) = match header.cartridge {
+            Cartridge::Mbc5Ram | Cartridge::Mbc5RumbleRam => (false, header.ram_size.get_size()),
+            Cartridge::Mbc5RamBattery | Cartridge::Mbc5RumbleRamBattery => {
+                (true, header.ram_size.get_size())
+            }
+            _ => (false, 0),
+        };
+        let rumble = match header.cartridge {
+            Cartridge::Mbc5Rumble | Cartridge::Mbc5RumbleRam | Cartridge::Mbc5RumbleRamBattery => {
+                Some(false)
+            }
+            _ => None,
+        };
 
-        mbc.set(0x034b, 0x03).unwrap();
-        assert!(!mbc.ram_lock)
+        let res = Mbc5 {
+            rom: data,
+            ram: vec![0; ramsize],
+            ram_on: false,
+            rombank: 1,
+            rambank: 0,
+            rumble,
+            battery,
+        };
+        Box::new(res)
     }
+}
 
-    #[test]
-    fn test_mbc5_reg1_0() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc5::new(rom_file);
+#[cfg(test)]
+mod mbc5_test {
+    use super::{Mbc, Mbc5};
+
+    /// 512 banks of ROM, the first byte of each bank holding its bank number
+    /// on 2 bytes, little endian
+    fn setup_mbc5() -> Mbc5 {
+        let mut mbc = Mbc5::default();
+        for bank in 0..0x200usize {
+            mbc.rom[bank * 0x4000] = bank as u8;
+            mbc.rom[bank * 0x4000 + 1] = (bank >> 8) as u8;
+        }
+        mbc
+    }
 
-        mbc.set(0x2156, 0x00).unwrap();
-        assert_eq!(mbc.rom_bank, 0x00);
+    fn upper_bank(mbc: &Mbc5) -> usize {
+        mbc.get_rom(0x4000).unwrap() as usize | (mbc.get_rom(0x4001).unwrap() as usize) << 8
     }
 
     #[test]
-    fn test_mbc5_reg1_ff() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc5::new(rom_file);
+    fn test_mbc5_default_bank_1() {
+        let mbc = setup_mbc5();
 
-        mbc.set(0x2156, 0xff).unwrap();
-        assert_eq!(mbc.rom_bank, 0xff);
+        assert_eq!(mbc.get_rom(0x0000).unwrap(), 0x00);
+        assert_eq!(upper_bank(&mbc), 0x01);
     }
 
     #[test]
-    fn test_mbc5_reg2_reg1_1ff() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc5::new(rom_file);
+    fn test_mbc5_lock() {
+        let mut mbc = setup_mbc5();
 
-        mbc.set(0x2fff, 0xff).unwrap();
-        assert_eq!(mbc.rom_bank, 0xff);
+        mbc.set_rom(0x01f5, 0x0a).unwrap();
+        assert!(mbc.ram_on);
 
-        mbc.set(0x3000, 0x01).unwrap();
-        assert_eq!(mbc.rom_bank, 0x1ff);
+        mbc.set_rom(0x1fff, 0x00).unwrap();
+        assert!(!mbc.ram_on)
     }
 
     #[test]
-    fn test_mbc5_get_rom_bank_1ff() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc5::new(rom_file);
+    fn test_mbc5_bank_0_in_upper_window() {
+        let mut mbc = setup_mbc5();
 
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
-
-        mbc.set(0x2fff, 0xff).unwrap();
-        assert_eq!(mbc.rom_bank, 0xff);
+        mbc.set_rom(0x2000, 0x00).unwrap();
+        assert_eq!(mbc.rombank, 0x00);
+        assert_eq!(upper_bank(&mbc), 0x00);
+    }
 
-        mbc.set(0x3000, 0x01).unwrap();
-        assert_eq!(mbc.rom_bank, 0x1ff);
+    #[test]
+    fn test_mbc5_9_bits_rom_bank() {
+        let mut mbc = setup_mbc5();
 
-        mbc.set(0xa042, 0x42).unwrap();
+        mbc.set_rom(0x2000, 0x42).unwrap();
+        mbc.set_rom(0x3000, 0x01).unwrap();
+        assert_eq!(upper_bank(&mbc), 0x142);
 
-        let data = mbc.get(0xa042);
-        assert_eq!(data.unwrap(), 0x42);
+        mbc.set_rom(0x2fff, 0xff).unwrap();
+        assert_eq!(upper_bank(&mbc), 0x1ff);
 
-        mbc.set(0x01f5, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        mbc.set_rom(0x3fff, 0xfe).unwrap();
+        assert_eq!(upper_bank(&mbc), 0x0ff);
     }
 
     #[test]
-    fn test_mbc5_reg3_get_ram_bank_ff() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc5::new(rom_file);
+    fn test_mbc5_16_ram_banks() {
+        let mut mbc = setup_mbc5();
 
-        mbc.set(0x4f4f, 0xff).unwrap();
-        assert_eq!(mbc.ram_bank, 0x0f);
+        mbc.set_rom(0x0000, 0x0a).unwrap();
+        for bank in 0..0x10 {
+            mbc.set_rom(0x4000, bank).unwrap();
+            mbc.set_ram(0xa123, bank + 0x40).unwrap();
+        }
+        for bank in 0..0x10 {
+            mbc.set_rom(0x5fff, bank).unwrap();
+            assert_eq!(mbc.get_ram(0xa123).unwrap(), bank + 0x40);
+        }
     }
 
     #[test]
-    fn test_mbc5_write_in_ram() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc5::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+    fn test_mbc5_locked_ram() {
+        let mut mbc = setup_mbc5();
 
-        mbc.set(0x4f4f, 0x00).unwrap();
-        assert_eq!(mbc.ram_bank, 0);
+        mbc.set_ram(0xa000, 0x12).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0xff);
 
-        mbc.set(0x0000a630, 0xca).unwrap();
+        mbc.set_rom(0x0000, 0x0a).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0x00);
+    }
 
-        let data = mbc.get(0x0000a630);
-        assert_eq!(data.unwrap(), 0xca);
+    #[test]
+    fn test_mbc5_no_rumble() {
+        let mut mbc = setup_mbc5();
 
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        mbc.set_rom(0x4000, 0x08).unwrap();
+        assert!(!mbc.rumble());
+        assert_eq!(mbc.rambank, 0x08);
     }
 
     #[test]
-    fn test_mbc5_change_ram_bank() {
-        let rom_file = FILE.to_vec();
-        let mut mbc = Mbc5::new(rom_file);
-
-        mbc.set(0x01f5, 0x0a).unwrap();
-        assert!(mbc.ram_lock);
+    fn test_mbc5_rumble_motor() {
+        let mut mbc = setup_mbc5();
+        mbc.rumble = Some(false);
 
-        mbc.set(0x4f4f, 0x01).unwrap();
-        assert_eq!(mbc.ram_bank, 1);
+        mbc.set_rom(0x4000, 0x0b).unwrap();
+        assert!(mbc.rumble());
+        assert_eq!(mbc.rambank, 0x03);
 
-        mbc.set(0x01ff, 0x00).unwrap();
-        assert!(!mbc.ram_lock);
+        mbc.set_rom(0x5fff, 0x03).unwrap();
+        assert!(!mbc.rumble());
+        assert_eq!(mbc.rambank, 0x03);
     }
 }
@@ -0,0 +1,511 @@
+use super::bus::Mbc;
+use super::consts;
+use crate::{Cartridge, Header};
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
+use std::convert::AsRef;
+
+#[derive(Debug)]
+pub struct Mbc7 {
+    rom: Vec<u8>,
+    /// Both enable registers must be set to access the A000-AFFF area:
+    /// 0x0A in 0000-1FFF and 0x40 in 4000-5FFF
+    ram_on: (bool, bool),
+    /// Max 128 0x00 ..= 0x7f
+    rombank: usize,
+    accelerometer: Accelerometer,
+    eeprom: Eeprom,
+    /// The EEPROM is saved, see `save::SaveFile`
+    battery: bool,
+}
+
+impl Default for Mbc7 {
+    fn default() -> Self {
+        Mbc7 {
+            rom: vec![0; consts::MBC7_MAX_SIZE],
+            ram_on: (false, false),
+            rombank: 1,
+            accelerometer: Accelerometer::default(),
+            eeprom: Eeprom::default(),
+            battery: false,
+        }
+    }
+}
+
+impl AsRef<Vec<u8>> for Mbc7 {
+    fn as_ref(&self) -> &Vec<u8> {
+        self.rom.as_ref()
+    }
+}
+
+/// 2 axis accelerometer, the values are only visible to the game once latched
+/// by writing 0x55 to Ax0x then 0xAA to Ax1x.
+#[derive(Debug)]
+struct Accelerometer {
+    /// Host tilt in g, from -1.0 to 1.0 on each axis
+    tilt: (f32, f32),
+    latched: (u16, u16),
+    erased: bool,
+}
+
+impl Default for Accelerometer {
+    fn default() -> Self {
+        Accelerometer {
+            tilt: (0.0, 0.0),
+            latched: (consts::MBC7_ACCEL_ERASED, consts::MBC7_ACCEL_ERASED),
+            erased: false,
+        }
+    }
+}
+
+impl Accelerometer {
+    fn erase(&mut self) {
+        self.latched = (consts::MBC7_ACCEL_ERASED, consts::MBC7_ACCEL_ERASED);
+        self.erased = true;
+    }
+
+    fn latch(&mut self) {
+        if !self.erased {
+            return;
+        }
+        let axis = |tilt: f32| {
+            (consts::MBC7_ACCEL_CENTER as f32 + tilt.clamp(-1.0, 1.0) * consts::MBC7_ACCEL_G) as u16
+        };
+        self.latched = (axis(self.tilt.0), axis(self.tilt.1));
+        self.erased = false;
+    }
+}
+
+/// State of the serial protocol, commands are a start bit, 2 bits of opcode
+/// and 8 bits of address, the 93LC56 ignoring the highest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transfer {
+    Idle,
+    Command {
+        bits: u8,
+        value: u16,
+    },
+    Read {
+        bits: u8,
+        value: u16,
+    },
+    Write {
+        address: Option<u8>,
+        bits: u8,
+        value: u16,
+    },
+}
+
+/// 93LC56 serial EEPROM, 128 words of 16 bits bit-banged through Ax8x:
+/// bit 7 => CS, bit 6 => CLK, bit 1 => DI, bit 0 => DO
+#[derive(Debug)]
+struct Eeprom {
+    /// Words stored little endian
+    data: Vec<u8>,
+    cs: bool,
+    clk: bool,
+    di: bool,
+    dout: bool,
+    write_enabled: bool,
+    transfer: Transfer,
+}
+
+impl Default for Eeprom {
+    fn default() -> Self {
+        Eeprom {
+            data: vec![0xFF; consts::MBC7_EEPROM_SIZE],
+            cs: false,
+            clk: false,
+            di: false,
+            dout: true,
+            write_enabled: false,
+            transfer: Transfer::Idle,
+        }
+    }
+}
+
+impl Eeprom {
+    fn get(&self) -> u8 {
+        (self.cs as u8) << 7 | (self.clk as u8) << 6 | (self.di as u8) << 1 | self.dout as u8
+    }
+
+    fn set(&mut self, data: u8) {
+        let (cs, clk, di) = (data & 0x80 != 0, data & 0x40 != 0, data & 0x02 != 0);
+        let rising = !self.clk && clk;
+        self.cs = cs;
+        self.clk = clk;
+        self.di = di;
+        if !cs {
+            self.transfer = Transfer::Idle;
+        } else if rising {
+            self.clock(di);
+        }
+    }
+
+    fn word(&self, address: u8) -> u16 {
+        let index = (address as usize & 0x7F) * 2;
+        u16::from_le_bytes([self.data[index], self.data[index + 1]])
+    }
+
+    fn set_word(&mut self, address: u8, value: u16) {
+        if !self.write_enabled {
+            return;
+        }
+        let index = (address as usize & 0x7F) * 2;
+        self.data[index..index + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Shift a bit in, or out for reads, on each rising edge of the clock
+    fn clock(&mut self, di: bool) {
+        self.transfer = match self.transfer {
+            Transfer::Idle if di => Transfer::Command { bits: 0, value: 0 },
+            Transfer::Idle => Transfer::Idle,
+            Transfer::Command { bits: 9, value } => self.command((value << 1) | di as u16),
+            Transfer::Command { bits, value } => Transfer::Command {
+                bits: bits + 1,
+                value: (value << 1) | di as u16,
+            },
+            Transfer::Read { bits, value } => {
+                self.dout = value & 0x8000 != 0;
+                match bits {
+                    15 => Transfer::Idle,
+                    _ => Transfer::Read {
+                        bits: bits + 1,
+                        value: value << 1,
+                    },
+                }
+            }
+            Transfer::Write {
+                address,
+                bits: 15,
+                value,
+            } => {
+                let value = (value << 1) | di as u16;
+                match address {
+                    Some(address) => self.set_word(address, value),
+                    None => (0..0x80).for_each(|address| self.set_word(address, value)),
+                }
+                self.dout = true;
+                Transfer::Idle
+            }
+            Transfer::Write {
+                address,
+                bits,
+                value,
+            } => Transfer::Write {
+                address,
+                bits: bits + 1,
+                value: (value << 1) | di as u16,
+            },
+        }
+    }
+
+    fn command(&mut self, command: u16) -> Transfer {
+        let address = (command & 0x7F) as u8;
+        match (command >> 8, (command >> 6) & 0x03) {
+            (0b10, _) => {
+                // A dummy 0 is output before the data
+                self.dout = false;
+                return Transfer::Read {
+                    bits: 0,
+                    value: self.word(address),
+                };
+            }
+            (0b01, _) => {
+                return Transfer::Write {
+                    address: Some(address),
+                    bits: 0,
+                    value: 0,
+                }
+            }
+            (0b11, _) => self.set_word(address, 0xFFFF),
+            (0b00, 0b11) => self.write_enabled = true,
+            (0b00, 0b00) => self.write_enabled = false,
+            (0b00, 0b10) => (0..0x80).for_each(|address| self.set_word(address, 0xFFFF)),
+            (0b00, _) => {
+                return Transfer::Write {
+                    address: None,
+                    bits: 0,
+                    value: 0,
+                }
+            }
+            _ => {}
+        };
+        self.dout = true;
+        Transfer::Idle
+    }
+}
+
+impl Mbc for Mbc7 {
+    fn get_rom(&self, address: usize) -> Result<u8, Error> {
+        let index = if address < 0x4000 {
+            address
+        } else {
+            (self.rombank * 0x4000) | (address & 0x3FFF)
+        };
+        Ok(*self.rom.get(index).unwrap_or(&0xFF))
+    }
+
+    fn set_rom(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_on.0 = data & 0x0F == 0x0A;
+            }
+            0x2000..=0x3FFF => {
+                self.rombank = (data as usize) & 0x7F;
+            }
+            0x4000..=0x5FFF => {
+                self.ram_on.1 = data == 0x40;
+            }
+            0x6000..=0x7FFF => {}
+            _ => return Err(Error::IllegalSet(address, data)),
+        };
+        Ok(())
+    }
+
+    /// Registers are selected by bits 4-7 of the address in A000-AFFF
+    fn get_ram(&self, address: usize) -> Result<u8, Error> {
+        if self.ram_on != (true, true) || address & 0x1000 != 0 {
+            return Ok(0xFF);
+        }
+        Ok(match (address >> 4) & 0x0F {
+            0x2 => self.accelerometer.latched.0 as u8,
+            0x3 => (self.accelerometer.latched.0 >> 8) as u8,
+            0x4 => self.accelerometer.latched.1 as u8,
+            0x5 => (self.accelerometer.latched.1 >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.get(),
+            _ => 0xFF,
+        })
+    }
+
+    fn set_ram(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        if self.ram_on != (true, true) || address & 0x1000 != 0 {
+            return Ok(());
+        }
+        match ((address >> 4) & 0x0F, data) {
+            (0x0, 0x55) => self.accelerometer.erase(),
+            (0x1, 0xAA) => self.accelerometer.latch(),
+            (0x8, _) => self.eeprom.set(data),
+            _ => {}
+        };
+        Ok(())
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.accelerometer.tilt = (x, y);
+    }
+
+    fn battery(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.eeprom.data.clone())
+    }
+
+    fn load_battery(&mut self, mut data: Vec<u8>) {
+        data.resize(self.eeprom.data.len(), 0xFF);
+        self.eeprom.data = data;
+    }
+}
+
+impl Snapshot for Mbc7 {
+    /// The host tilt is not part of the state, only the latched values
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.bool(self.ram_on.0);
+        writer.bool(self.ram_on.1);
+        writer.usize(self.rombank);
+        writer.u16(self.accelerometer.latched.0);
+        writer.u16(self.accelerometer.latched.1);
+        writer.bool(self.accelerometer.erased);
+        self.eeprom.snapshot(writer);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.ram_on = (reader.bool()?, reader.bool()?);
+        self.rombank = reader.usize()?;
+        self.accelerometer.latched = (reader.u16()?, reader.u16()?);
+        self.accelerometer.erased = reader.bool()?;
+        self.eeprom.restore(reader)
+    }
+}
+
+impl Snapshot for Eeprom {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.bytes(&self.data);
+        writer.bool(self.cs);
+        writer.bool(self.clk);
+        writer.bool(self.di);
+        writer.bool(self.dout);
+        writer.bool(self.write_enabled);
+        let (kind, address, bits, value) = match self.transfer {
+            Transfer::Idle => (0, None, 0, 0),
+            Transfer::Command { bits, value } => (1, None, bits, value),
+            Transfer::Read { bits, value } => (2, None, bits, value),
+            Transfer::Write {
+                address,
+                bits,
+                value,
+            } => (3, address, bits, value),
+        };
+        writer.u8(kind);
+        writer.bool(address.is_some());
+        writer.u8(address.unwrap_or(0));
+        writer.u8(bits);
+        writer.u16(value);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        reader.bytes_into(&mut self.data)?;
+        self.cs = reader.bool()?;
+        self.clk = reader.bool()?;
+        self.di = reader.bool()?;
+        self.dout = reader.bool()?;
+        self.write_enabled = reader.bool()?;
+        let kind = reader.u8()?;
+        let address = match (reader.bool()?, reader.u8()?) {
+            (true, address) => Some(address),
+            (false, _) => None,
+        };
+        let (bits, value) = (reader.u8()?, reader.u16()?);
+        self.transfer = match kind {
+            0 => Transfer::Idle,
+            1 => Transfer::Command { bits, value },
+            2 => Transfer::Read { bits, value },
+            3 => Transfer::Write {
+                address,
+                bits,
+                value,
+            },
+            _ => return Err(Error::InvalidState),
+        };
+        Ok(())
+    }
+}
+
+impl Mbc7 {
+    pub fn new(header: Header, data: Vec<u8>) -> Box<Self> {
+        let battery = matches!(header.cartridge, Cartridge::Mbc7SensorRumbleRamBattery);
+
+        let res = Mbc7 {
+            rom: data,
+            ram_on: (false, false),
+            rombank: 1,
+            accelerometer: Accelerometer::default(),
+            eeprom: Eeprom::default(),
+            battery,
+        };
+        Box::new(res)
+    }
+}
+
+#[cfg(test)]
+mod mbc7_test {
+    use super::{Mbc, Mbc7};
+
+    fn setup_mbc7() -> Mbc7 {
+        let mut mbc = Mbc7::default();
+        mbc.set_rom(0x0000, 0x0a).unwrap();
+        mbc.set_rom(0x4000, 0x40).unwrap();
+        mbc
+    }
+
+    /// Clock the bits in, MSB first, and return what DO output on each rising edge
+    fn send(mbc: &mut Mbc7, value: u32, len: u8) -> u32 {
+        let mut output = 0;
+        for bit in (0..len).rev() {
+            let di = ((value >> bit) & 1) as u8;
+            mbc.set_ram(0xa080, 0x80 | di << 1).unwrap();
+            mbc.set_ram(0xa080, 0xc0 | di << 1).unwrap();
+            output = (output << 1) | (mbc.get_ram(0xa080).unwrap() & 1) as u32;
+        }
+        output
+    }
+
+    fn deselect(mbc: &mut Mbc7) {
+        mbc.set_ram(0xa080, 0x00).unwrap();
+    }
+
+    fn read_word(mbc: &mut Mbc7, address: u32) -> u16 {
+        send(mbc, 0b110 << 8 | address, 11);
+        let word = send(mbc, 0, 16) as u16;
+        deselect(mbc);
+        word
+    }
+
+    fn write_word(mbc: &mut Mbc7, address: u32, value: u32) {
+        send(mbc, (0b101 << 8 | address) << 16 | value, 27);
+        deselect(mbc);
+    }
+
+    #[test]
+    fn test_mbc7_needs_both_enables() {
+        let mut mbc = Mbc7::default();
+
+        mbc.set_rom(0x0000, 0x0a).unwrap();
+        assert_eq!(mbc.get_ram(0xa060).unwrap(), 0xff);
+
+        mbc.set_rom(0x4000, 0x40).unwrap();
+        assert_eq!(mbc.get_ram(0xa060).unwrap(), 0x00);
+    }
+
+    #[test]
+    fn test_mbc7_accelerometer_latch() {
+        let mut mbc = setup_mbc7();
+
+        mbc.set_tilt(1.0, -0.5);
+        mbc.set_ram(0xa000, 0x55).unwrap();
+        assert_eq!(mbc.get_ram(0xa030).unwrap(), 0x80);
+        assert_eq!(mbc.get_ram(0xa020).unwrap(), 0x00);
+
+        mbc.set_ram(0xa010, 0xaa).unwrap();
+        let x = mbc.get_ram(0xa020).unwrap() as u16 | (mbc.get_ram(0xa030).unwrap() as u16) << 8;
+        let y = mbc.get_ram(0xa040).unwrap() as u16 | (mbc.get_ram(0xa050).unwrap() as u16) << 8;
+        assert_eq!(x, 0x81d0 + 0x70);
+        assert_eq!(y, 0x81d0 - 0x38);
+    }
+
+    #[test]
+    fn test_mbc7_latch_needs_erase() {
+        let mut mbc = setup_mbc7();
+
+        mbc.set_ram(0xa000, 0x55).unwrap();
+        mbc.set_ram(0xa010, 0xaa).unwrap();
+        mbc.set_tilt(1.0, 1.0);
+        mbc.set_ram(0xa010, 0xaa).unwrap();
+
+        assert_eq!(mbc.get_ram(0xa020).unwrap(), 0xd0);
+    }
+
+    #[test]
+    fn test_mbc7_eeprom_write_disabled() {
+        let mut mbc = setup_mbc7();
+
+        write_word(&mut mbc, 0x12, 0xbeef);
+        assert_eq!(read_word(&mut mbc, 0x12), 0xffff);
+    }
+
+    #[test]
+    fn test_mbc7_eeprom_write_read() {
+        let mut mbc = setup_mbc7();
+
+        send(&mut mbc, 0b100_1100_0000, 11);
+        deselect(&mut mbc);
+        write_word(&mut mbc, 0x12, 0xbeef);
+        write_word(&mut mbc, 0x7f, 0x1234);
+
+        assert_eq!(read_word(&mut mbc, 0x12), 0xbeef);
+        assert_eq!(read_word(&mut mbc, 0x7f), 0x1234);
+        assert_eq!(&mbc.eeprom.data[0x24..0x26], &[0xef, 0xbe]);
+    }
+
+    #[test]
+    fn test_mbc7_eeprom_erase_all() {
+        let mut mbc = setup_mbc7();
+
+        send(&mut mbc, 0b100_1100_0000, 11);
+        deselect(&mut mbc);
+        write_word(&mut mbc, 0x00, 0x0000);
+        send(&mut mbc, 0b100_1000_0000, 11);
+        deselect(&mut mbc);
+
+        assert_eq!(read_word(&mut mbc, 0x00), 0xffff);
+    }
+}
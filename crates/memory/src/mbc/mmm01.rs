@@ -0,0 +1,246 @@
+use super::bus::Mbc;
+use super::consts;
+use crate::{Cartridge, Header};
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
+use std::convert::AsRef;
+
+#[derive(Debug)]
+pub struct Mmm01 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_on: bool,
+    /// Until bit 6 of 0000-1FFF is set the menu in the last 32 KiB is mapped,
+    /// then the selected sub-game is locked in until the next reset.
+    mapped: bool,
+    /// Max 512 0x000 ..= 0x1ff
+    rombank: usize,
+    /// Bits 1-4 of the rom bank frozen once mapped, from bits 2-5 of 6000-7FFF
+    rom_mask: usize,
+    /// Max 16 0x0 ..= 0xf
+    rambank: usize,
+    /// The RAM is kept by a battery, see `save::SaveFile`
+    battery: bool,
+}
+
+impl Default for Mmm01 {
+    fn default() -> Self {
+        Mmm01 {
+            rom: vec![0; consts::MMM01_MAX_SIZE],
+            ram: vec![0; consts::MMM01_RAM_SIZE],
+            ram_on: false,
+            mapped: false,
+            rombank: 0,
+            rom_mask: 0,
+            rambank: 0,
+            battery: false,
+        }
+    }
+}
+
+impl AsRef<Vec<u8>> for Mmm01 {
+    fn as_ref(&self) -> &Vec<u8> {
+        self.rom.as_ref()
+    }
+}
+
+impl Mbc for Mmm01 {
+    fn get_rom(&self, address: usize) -> Result<u8, Error> {
+        let index = if !self.mapped {
+            let menu = self.rom.len().saturating_sub(consts::MMM01_MENU_SIZE);
+            menu + (address & 0x7FFF)
+        } else if address < 0x4000 {
+            ((self.rombank & !self.writable()) * 0x4000) | address
+        } else {
+            let bank = match self.rombank & self.writable() {
+                0 => self.rombank | 1,
+                _ => self.rombank,
+            };
+            (bank * 0x4000) | (address & 0x3FFF)
+        };
+        Ok(*self.rom.get(index).unwrap_or(&0xFF))
+    }
+
+    fn set_rom(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        let data = data as usize;
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_on = data & 0x0F == 0x0A;
+                if !self.mapped {
+                    self.mapped = data & 0x40 != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                let writable = match self.mapped {
+                    false => 0x7F,
+                    true => self.writable(),
+                };
+                self.rombank = (self.rombank & !writable) | (data & writable);
+            }
+            0x4000..=0x5FFF => {
+                if self.mapped {
+                    self.rambank = (self.rambank & 0x0C) | (data & 0x03);
+                } else {
+                    self.rambank = data & 0x0F;
+                    self.rombank = (self.rombank & 0x7F) | ((data & 0x30) << 3);
+                }
+            }
+            0x6000..=0x7FFF => {
+                if !self.mapped {
+                    self.rom_mask = (data >> 2) & 0x0F;
+                }
+            }
+            _ => panic!("Could not write to {:04X} (MMM01)", address),
+        };
+        Ok(())
+    }
+
+    fn get_ram(&self, address: usize) -> Result<u8, Error> {
+        if !self.ram_on {
+            return Ok(0xFF);
+        }
+        Ok(*self
+            .ram
+            .get((self.rambank * 0x2000) | (address & 0x1FFF))
+            .unwrap_or(&0xFF))
+    }
+
+    fn set_ram(&mut self, address: usize, data: u8) -> Result<(), Error> {
+        if !self.ram_on {
+            return Ok(());
+        }
+        if let Some(byte) = self
+            .ram
+            .get_mut((self.rambank * 0x2000) | (address & 0x1FFF))
+        {
+            *byte = data;
+        }
+        Ok(())
+    }
+
+    fn battery(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_battery(&mut self, mut data: Vec<u8>) {
+        data.resize(self.ram.len(), 0);
+        self.ram = data;
+    }
+}
+
+impl Snapshot for Mmm01 {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.bytes(&self.ram);
+        writer.bool(self.ram_on);
+        writer.bool(self.mapped);
+        writer.usize(self.rombank);
+        writer.usize(self.rom_mask);
+        writer.usize(self.rambank);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        reader.bytes_into(&mut self.ram)?;
+        self.ram_on = reader.bool()?;
+        self.mapped = reader.bool()?;
+        self.rombank = reader.usize()?;
+        self.rom_mask = reader.usize()?;
+        self.rambank = reader.usize()?;
+        Ok(())
+    }
+}
+
+impl Mmm01 {
+    pub fn new(header: Header, data: Vec<u8>) -> Box<Self> {
+        let battery = matches!(header.cartridge, Cartridge::Mmm01RamBattery);
+
+        let res = Mmm01 {
+            rom: data,
+            ram: vec![0; header.ram_size.get_size()],
+            ram_on: false,
+            mapped: false,
+            rombank: 0,
+            rom_mask: 0,
+            rambank: 0,
+            battery,
+        };
+        Box::new(res)
+    }
+
+    /// Rom bank bits the sub-game can still switch once mapped
+    fn writable(&self) -> usize {
+        0x1F & !(self.rom_mask << 1)
+    }
+}
+
+#[cfg(test)]
+mod mmm01_test {
+    use super::{Mbc, Mmm01};
+
+    fn setup_mmm01() -> Mmm01 {
+        Mmm01 {
+            rom: (0..64u8).flat_map(|bank| vec![bank; 0x4000]).collect(),
+            ram: vec![0; 0x8000],
+            ..Default::default()
+        }
+    }
+
+    /// Lock in the 8 banks sub-game starting at bank 8
+    fn map_game(mbc: &mut Mmm01) {
+        mbc.set_rom(0x2000, 0x08).unwrap();
+        mbc.set_rom(0x6000, 0x30).unwrap();
+        mbc.set_rom(0x0000, 0x4a).unwrap();
+    }
+
+    #[test]
+    fn test_mmm01_boots_the_menu() {
+        let mut mbc = setup_mmm01();
+
+        assert_eq!(mbc.get_rom(0x0100).unwrap(), 62);
+        assert_eq!(mbc.get_rom(0x4000).unwrap(), 63);
+        mbc.set_rom(0x2000, 0x05).unwrap();
+        assert_eq!(mbc.get_rom(0x4000).unwrap(), 63);
+    }
+
+    #[test]
+    fn test_mmm01_maps_the_game() {
+        let mut mbc = setup_mmm01();
+        map_game(&mut mbc);
+
+        assert_eq!(mbc.get_rom(0x0000).unwrap(), 8);
+        assert_eq!(mbc.get_rom(0x4000).unwrap(), 9);
+        mbc.set_rom(0x2000, 0x03).unwrap();
+        assert_eq!(mbc.get_rom(0x4000).unwrap(), 11);
+        mbc.set_rom(0x2000, 0x1f).unwrap();
+        assert_eq!(mbc.get_rom(0x7fff).unwrap(), 15);
+        mbc.set_rom(0x2000, 0x00).unwrap();
+        assert_eq!(mbc.get_rom(0x4000).unwrap(), 9);
+    }
+
+    #[test]
+    fn test_mmm01_mapping_is_locked() {
+        let mut mbc = setup_mmm01();
+        map_game(&mut mbc);
+
+        mbc.set_rom(0x6000, 0x00).unwrap();
+        mbc.set_rom(0x0000, 0x00).unwrap();
+        mbc.set_rom(0x2000, 0x1f).unwrap();
+        assert_eq!(mbc.get_rom(0x0000).unwrap(), 8);
+        assert_eq!(mbc.get_rom(0x4000).unwrap(), 15);
+    }
+
+    #[test]
+    fn test_mmm01_ram_banks() {
+        let mut mbc = setup_mmm01();
+        mbc.set_rom(0x4000, 0x02).unwrap();
+        map_game(&mut mbc);
+
+        mbc.set_ram(0xa000, 0x42).unwrap();
+        mbc.set_rom(0x4000, 0x01).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0x00);
+        mbc.set_rom(0x4000, 0x02).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0x42);
+
+        mbc.set_rom(0x0000, 0x00).unwrap();
+        assert_eq!(mbc.get_ram(0xa000).unwrap(), 0xff);
+    }
+}
@@ -1,19 +1,25 @@
 use std::cell::RefCell;
-use std::path;
 use std::rc::Rc;
+use std::{io, path};
 
-use super::mbc::{Cartridge, Mbc0, Mbc1}; // Mbc2, Mbc3, Mbc5};
+use super::mbc::{
+    camera, Cartridge, HuC1, HuC3, Infrared, Mbc0, Mbc1, Mbc2, Mbc3, Mbc5, Mbc7, Mmm01,
+    PocketCamera,
+};
 use crate::area::Area;
 use crate::bios::Bios;
 use crate::bus::MemoryBus;
+use crate::dma::Dma;
 use crate::interface::{Bus, Rom};
 use crate::interrupts::Interrupts;
 use crate::io::IO;
 use crate::mbc::default::RomDefault;
 use crate::ram::Ram;
+use crate::save::SaveFile;
 use crate::state::{self, State};
 use crate::{consts::*, Header};
 use ppu::Ppu;
+use shared::snapshot::{Reader, Snapshot, Writer};
 use shared::Error;
 
 #[derive(Debug)]
@@ -26,6 +32,10 @@ pub struct Memory {
     pub(crate) hram: Bus,
     pub(crate) io: IO,
     pub(crate) interrupts: Interrupts,
+    pub(crate) dma: Dma,
+    pub(crate) save: Option<SaveFile>,
+    /// Serial output kept for the host, see `capture_serial`
+    pub(crate) serial: Option<Vec<u8>>,
 }
 
 impl Default for Memory {
@@ -43,13 +53,24 @@ impl Default for Memory {
             rom: Rom::default(),
             io,
             hram: Rc::new(RefCell::new(Box::new(Ram::new(127)))),
+            save: None,
+            serial: None,
             interrupts,
+            dma: Dma::default(),
         }
     }
 }
 
 impl Memory {
+    /// Only HRAM and the IO registers answer the CPU while the DMA runs
     pub fn get_u8(&self, address: u16) -> Result<u8, Error> {
+        match self.dma.blocks(address) {
+            true => Ok(0xFF),
+            false => self.read_u8(address),
+        }
+    }
+
+    fn read_u8(&self, address: u16) -> Result<u8, Error> {
         match address {
             BIOS_MIN..=BIOS_MAX if self.state == state::State::Bios => {
                 self.bios.borrow().get(Area::Bios.relative(address))
@@ -70,13 +91,21 @@ impl Memory {
     fn get_io(&self, address: u16) -> Result<u8, Error> {
         match address {
             LCD_CONTROL..=LY_COMPARE => self.ppu.borrow_mut().get(address.into()),
-            YWINDOW | XWINDOW | BGP => self.ppu.borrow_mut().get(address.into()),
+            YWINDOW | XWINDOW | BGP | OBP0 | OBP1 => self.ppu.borrow_mut().get(address.into()),
             INTERRUPT_FLAGS => self.interrupts.get_requested(),
+            DMA_TRANSFERT => Ok(self.dma.get()),
             _ => Ok(self.io.get(address)),
         }
     }
 
     pub fn set_u8(&mut self, address: u16, data: u8) -> Result<(), Error> {
+        match self.dma.blocks(address) {
+            true => Ok(()),
+            false => self.write_u8(address, data),
+        }
+    }
+
+    fn write_u8(&mut self, address: u16, data: u8) -> Result<(), Error> {
         match address {
             BIOS_MIN..=BIOS_MAX if self.state == state::State::Bios => self
                 .bios
@@ -110,9 +139,14 @@ impl Memory {
     fn set_io(&mut self, address: u16, data: u8) -> Result<(), Error> {
         match address {
             BIOS_DISABLE => self.state.disable_bios(),
-            DMA_TRANSFERT => self.dma_transfert(data),
+            DMA_TRANSFERT => {
+                self.dma.set(data);
+                Ok(())
+            }
             LCD_CONTROL..=LY_COMPARE => self.ppu.borrow_mut().set(address.into(), data),
-            YWINDOW | XWINDOW | BGP => self.ppu.borrow_mut().set(address.into(), data),
+            YWINDOW | XWINDOW | BGP | OBP0 | OBP1 => {
+                self.ppu.borrow_mut().set(address.into(), data)
+            }
             INTERRUPT_FLAGS => self.interrupts.set_requested(data),
             _ => self.io.set(address, data),
         }
@@ -158,49 +192,152 @@ impl Memory {
         self.rom.clone()
     }
 
+    pub fn get_rumble(&self) -> bool {
+        self.rom.borrow().rumble()
+    }
+
+    pub fn set_tilt(&self, x: f32, y: f32) {
+        self.rom.borrow_mut().set_tilt(x, y)
+    }
+
+    /// Feed a PNG to the camera sensor, scaled to 128x112 grayscale
+    pub fn set_camera_image(&self, image: &path::Path) -> std::io::Result<()> {
+        let image = camera::load_png(image)?;
+        self.rom.borrow_mut().set_sensor(image);
+        Ok(())
+    }
+
+    /// Write the photos saved in the cartridge RAM as photo_XX.png into `directory`
+    pub fn export_photos(&self, directory: &path::Path) -> std::io::Result<Vec<path::PathBuf>> {
+        let photos = self.rom.borrow().photos();
+        photos
+            .iter()
+            .enumerate()
+            .map(|(slot, photo)| {
+                let file = directory.join(format!("photo_{:02}.png", slot + 1));
+                camera::save_png(&file, photo).map(|_| file)
+            })
+            .collect()
+    }
+
+    pub fn set_infrared(&self, peer: Box<dyn Infrared>) {
+        self.rom.borrow_mut().set_infrared(peer)
+    }
+
     pub fn clock_tick(&mut self) {
-        self.io.tick()
+        self.io.tick();
+        if let Err(e) = self.dma_tick() {
+            eprintln!("Could not copy the DMA byte: {}", e);
+        }
+        self.rom.borrow_mut().tick();
+        if self.save.as_mut().is_some_and(SaveFile::tick) {
+            if let Err(e) = self.flush_save() {
+                eprintln!("Could not write the save: {}", e);
+            }
+        }
     }
 
-    fn dma_transfert(&mut self, data: u8) -> Result<(), Error> {
-        let start = (data as u16) << 8;
-        for i in 0..DMA_LEN {
-            let byte = self.get_u8(start + i as u16)?;
-            let _ = self.set_u8(OAM_MIN + i as u16, byte);
+    /// Write the battery RAM to the save file if it changed
+    pub fn flush_save(&mut self) -> io::Result<()> {
+        match (self.save.as_mut(), self.rom.borrow().battery()) {
+            (Some(save), Some(data)) => save.flush(&data),
+            _ => Ok(()),
+        }
+    }
+
+    /// The DMA reads past its own bus restriction and writes OAM even while
+    /// the PPU locks it from the CPU
+    fn dma_tick(&mut self) -> Result<(), Error> {
+        match self.dma.tick() {
+            Some((source, destination)) => {
+                let byte = self.read_u8(source)?;
+                self.ppu.borrow_mut().set_oam(destination, byte)
+            }
+            None => Ok(()),
         }
-        Ok(())
     }
 
+    /// The byte sent over the serial port, printed unless the output is captured
     pub fn get_debug(&mut self) -> Option<char> {
         use std::io::Write;
         if self.io.get(SERIAL_CONTROL) == 0x81 {
             let data = self.io.get(SERIAL_DATA);
             let _ = self.io.set(SERIAL_CONTROL, 0);
-            print!("{}", data as char);
-            let _ = ::std::io::stdout().flush();
-            None
+            match self.serial.as_mut() {
+                Some(serial) => serial.push(data),
+                None => {
+                    print!("{}", data as char);
+                    let _ = ::std::io::stdout().flush();
+                }
+            }
+            Some(data as char)
         } else {
             None
         }
     }
+
+    /// Keep the serial output for `take_serial` instead of printing it
+    pub fn capture_serial(&mut self) {
+        self.serial.get_or_insert_with(Vec::new);
+    }
+
+    /// Drain the serial output captured since the last call
+    pub fn take_serial(&mut self) -> Vec<u8> {
+        self.serial.as_mut().map(std::mem::take).unwrap_or_default()
+    }
 }
 
 impl Memory {
-    pub fn new(header: Header, data: Vec<u8>, state: State) -> Rc<RefCell<Self>> {
-        let savepath = path::PathBuf::from(format!("/tmp/{}", header.title.get()));
+    /// The machine boots through `bios` when one is given, else it starts on the cartridge
+    pub fn new(
+        header: Header,
+        data: Vec<u8>,
+        bios: Option<Vec<u8>>,
+        save: SaveFile,
+    ) -> Rc<RefCell<Self>> {
         let rom: Rom = Rc::new(RefCell::new(match header.cartridge {
             Cartridge::Mbc0 => Mbc0::new(data),
-            Cartridge::Mbc1 => Mbc1::new(header, data, savepath),
-            //Cartridge::Mbc2 | Cartridge::Mbc2Battery => Mbc2::new(data),
-            //Cartridge::Mbc3 => Mbc3::new(data),
-            //Cartridge::Mbc5 => Mbc5::new(data),
+            Cartridge::Mbc1 => Mbc1::new(header, data),
+            Cartridge::Mbc2 | Cartridge::Mbc2Battery => Mbc2::new(header, data),
+            Cartridge::Mbc3TimerBattery
+            | Cartridge::Mbc3TimerRamBattery2
+            | Cartridge::Mbc3
+            | Cartridge::Mbc3Ram2
+            | Cartridge::Mbc3RamBattery2 => Mbc3::new(header, data),
+            Cartridge::Mbc5
+            | Cartridge::Mbc5Ram
+            | Cartridge::Mbc5RamBattery
+            | Cartridge::Mbc5Rumble
+            | Cartridge::Mbc5RumbleRam
+            | Cartridge::Mbc5RumbleRamBattery => Mbc5::new(header, data),
+            Cartridge::Mmm01 | Cartridge::Mmm01Ram | Cartridge::Mmm01RamBattery => {
+                Mmm01::new(header, data)
+            }
+            Cartridge::Mbc7SensorRumbleRamBattery => Mbc7::new(header, data),
+            Cartridge::PocketCamera => PocketCamera::new(header, data),
+            Cartridge::HuC1RamBattery => HuC1::new(header, data),
+            Cartridge::HuC3 => HuC3::new(header, data),
             _ => unimplemented!(),
         }));
+        // Restore the battery save, a save that could not be read is never overwritten
+        let mut save = Some(save).filter(|_| rom.borrow().battery().is_some());
+        match save.as_mut().map(SaveFile::load) {
+            Some(Ok(Some(data))) => rom.borrow_mut().load_battery(data),
+            Some(Err(e)) => {
+                eprintln!("Could not load the save: {}", e);
+                save = None;
+            }
+            _ => {}
+        }
+
         // Init state
-        let state = state;
+        let state = match bios {
+            Some(_) => State::Bios,
+            None => State::Rom,
+        };
 
         // Init Bios
-        let bios: Box<dyn MemoryBus> = Box::new(Bios::new());
+        let bios: Box<dyn MemoryBus> = Box::new(Bios::from(bios.unwrap_or_default()));
         let bios = Rc::new(RefCell::new(bios));
 
         // Init Wram
@@ -234,11 +371,69 @@ impl Memory {
             io,
             hram,
             interrupts,
+            dma: Dma::default(),
+            save,
+            serial: None,
         };
         Rc::new(RefCell::new(init))
     }
 }
 
+/// The BIOS is read only and the battery save belongs to the host, everything
+/// else is part of the save states.
+impl Snapshot for Memory {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.bool(self.state == State::Bios);
+        snapshot_bus(&self.wram, writer);
+        snapshot_bus(&self.hram, writer);
+        self.ppu.borrow().snapshot(writer);
+        self.io.snapshot(writer);
+        self.interrupts.snapshot(writer);
+        self.dma.snapshot(writer);
+        self.rom.borrow().snapshot(writer);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.state = match reader.bool()? {
+            true => State::Bios,
+            false => State::Rom,
+        };
+        restore_bus(&self.wram, reader)?;
+        restore_bus(&self.hram, reader)?;
+        self.ppu.borrow_mut().restore(reader)?;
+        self.io.restore(reader)?;
+        self.interrupts.restore(reader)?;
+        self.dma.restore(reader)?;
+        self.rom.borrow_mut().restore(reader)
+    }
+}
+
+fn snapshot_bus(bus: &Bus, writer: &mut Writer) {
+    let bus = bus.borrow();
+    let data: &Vec<u8> = (**bus).as_ref();
+    writer.bytes(data);
+}
+
+fn restore_bus(bus: &Bus, reader: &mut Reader) -> Result<(), Error> {
+    let data = reader.bytes()?;
+    let mut bus = bus.borrow_mut();
+    let len = AsRef::<Vec<u8>>::as_ref(&**bus).len();
+    if data.len() != len {
+        return Err(Error::InvalidState);
+    }
+    data.into_iter()
+        .enumerate()
+        .try_for_each(|(address, byte)| bus.set(address, byte))
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_save() {
+            eprintln!("Could not write the save: {}", e);
+        }
+    }
+}
+
 // Interrupt interface
 impl Memory {
     pub fn master_enabled(&self) -> bool {
@@ -290,6 +485,8 @@ impl Memory {
 
 #[cfg(test)]
 mod test_memory {
+    use ppu::registers::Mode;
+
     #[test]
     fn test_read_wram() {
         let memory = super::Memory::default();
@@ -327,4 +524,82 @@ mod test_memory {
         assert!(read.is_ok());
         assert_eq!(read.unwrap(), 0x2242);
     }
+
+    #[test]
+    fn test_vram_locked_in_transfert() {
+        let mut memory = super::Memory::default();
+        memory.set_u8(0x8000, 42).unwrap();
+
+        memory.ppu.borrow_mut().registers.mode = Mode::Transfert;
+        assert_eq!(memory.get_u8(0x8000).unwrap(), 0xFF);
+        memory.set_u8(0x8000, 24).unwrap();
+
+        memory.ppu.borrow_mut().registers.mode = Mode::Oam;
+        assert_eq!(memory.get_u8(0x8000).unwrap(), 42);
+    }
+
+    #[test]
+    fn test_oam_locked_in_scan_and_transfert() {
+        let mut memory = super::Memory::default();
+        memory.set_u8(0xFE00, 42).unwrap();
+
+        for mode in [Mode::Oam, Mode::Transfert] {
+            memory.ppu.borrow_mut().registers.mode = mode;
+            assert_eq!(memory.get_u8(0xFE00).unwrap(), 0xFF);
+            memory.set_u8(0xFE00, 24).unwrap();
+        }
+
+        for mode in [Mode::Hblank(204), Mode::Vblank] {
+            memory.ppu.borrow_mut().registers.mode = mode;
+            assert_eq!(memory.get_u8(0xFE00).unwrap(), 42);
+        }
+    }
+
+    #[test]
+    fn test_dma_writes_locked_oam() {
+        let mut memory = super::Memory::default();
+        memory.set_u8(0xC000, 42).unwrap();
+        memory.ppu.borrow_mut().registers.mode = Mode::Oam;
+
+        memory.set_u8(0xFF46, 0xC0).unwrap();
+        (0..644).for_each(|_| memory.clock_tick());
+
+        memory.ppu.borrow_mut().registers.mode = Mode::Vblank;
+        assert_eq!(memory.get_u8(0xFE00).unwrap(), 42);
+    }
+
+    #[test]
+    fn test_dma_copies_a_byte_per_m_cycle() {
+        let mut memory = super::Memory::default();
+        memory.set_u8(0xC000, 42).unwrap();
+        memory.set_u8(0xC001, 24).unwrap();
+
+        memory.set_u8(0xFF46, 0xC0).unwrap();
+        assert_eq!(memory.get_u8(0xFF46).unwrap(), 0xC0);
+        (0..8).for_each(|_| memory.clock_tick());
+        assert_eq!(memory.read_u8(0xFE00).unwrap(), 42);
+        assert_eq!(memory.read_u8(0xFE01).unwrap(), 0);
+
+        (0..4).for_each(|_| memory.clock_tick());
+        assert_eq!(memory.read_u8(0xFE01).unwrap(), 24);
+    }
+
+    #[test]
+    fn test_dma_leaves_only_hram_to_the_cpu() {
+        let mut memory = super::Memory::default();
+        memory.set_u8(0xC000, 42).unwrap();
+        memory.set_u8(0xFF80, 42).unwrap();
+
+        memory.set_u8(0xFF46, 0xC0).unwrap();
+        (0..4).for_each(|_| memory.clock_tick());
+        assert_eq!(memory.get_u8(0xC000).unwrap(), 0xFF);
+        assert_eq!(memory.get_u8(0xFE00).unwrap(), 0xFF);
+        assert_eq!(memory.get_u8(0xFF80).unwrap(), 42);
+        memory.set_u8(0xC000, 24).unwrap();
+        memory.set_u8(0xFF80, 24).unwrap();
+
+        (0..640).for_each(|_| memory.clock_tick());
+        assert_eq!(memory.get_u8(0xC000).unwrap(), 42);
+        assert_eq!(memory.get_u8(0xFF80).unwrap(), 24);
+    }
 }
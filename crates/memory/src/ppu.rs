@@ -7,17 +7,18 @@ impl MemoryBus for Ppu {
     fn get(&self, address: usize) -> Result<u8, Error> {
         let address = address as u16;
         match address {
-            consts::VRAM_MIN..=consts::VRAM_MAX => {
-                if self.vram_lock {
-                    println!("[CPU] Vram Locked");
-                    Ok(0xFF)
-                } else {
-                    self.get_vram(address)
-                }
-            }
-            consts::OAM_MIN..=consts::OAM_MAX => self.get_oam(address),
+            consts::VRAM_MIN..=consts::VRAM_MAX => match self.vram_locked() {
+                true => Ok(0xFF),
+                false => self.get_vram(address),
+            },
+            consts::OAM_MIN..=consts::OAM_MAX => match self.oam_locked() {
+                true => Ok(0xFF),
+                false => self.get_oam(address),
+            },
             consts::LCD_CONTROL..=consts::LY_COMPARE => self.get_registers(address),
-            consts::YWINDOW | consts::XWINDOW | consts::BGP => self.get_registers(address),
+            consts::YWINDOW | consts::XWINDOW | consts::BGP | consts::OBP0 | consts::OBP1 => {
+                self.get_registers(address)
+            }
             _ => unreachable!(),
         }
     }
@@ -25,17 +26,18 @@ impl MemoryBus for Ppu {
     fn set(&mut self, address: usize, data: u8) -> Result<(), Error> {
         let address = address as u16;
         match address {
-            consts::VRAM_MIN..=consts::VRAM_MAX => {
-                if self.vram_lock {
-                    println!("[CPU] Vram Locked");
-                    Ok(())
-                } else {
-                    self.set_vram(address, data)
-                }
-            }
-            consts::OAM_MIN..=consts::OAM_MAX => self.set_oam(address, data),
+            consts::VRAM_MIN..=consts::VRAM_MAX => match self.vram_locked() {
+                true => Ok(()),
+                false => self.set_vram(address, data),
+            },
+            consts::OAM_MIN..=consts::OAM_MAX => match self.oam_locked() {
+                true => Ok(()),
+                false => self.set_oam(address, data),
+            },
             consts::LCD_CONTROL..=consts::LY_COMPARE => self.set_registers(address, data),
-            consts::YWINDOW | consts::XWINDOW | consts::BGP => self.set_registers(address, data),
+            consts::YWINDOW | consts::XWINDOW | consts::BGP | consts::OBP0 | consts::OBP1 => {
+                self.set_registers(address, data)
+            }
             _ => unreachable!(),
         }
     }
@@ -0,0 +1,196 @@
+use crate::Header;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// T-cycles between two flushes of the battery RAM, about a second
+const FLUSH_INTERVAL: usize = 4_194_304;
+
+/// How the save file of a ROM is named
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveKey {
+    /// `<rom file name>.sav`, as most emulators do
+    Path,
+    /// `<title>-<global checksum>.sav`, follows the ROM when it is renamed or moved
+    Checksum,
+}
+
+/// Where the battery saves live, by default a `.sav` next to each ROM
+#[derive(Debug, Clone)]
+pub struct SaveManager {
+    directory: Option<PathBuf>,
+    key: SaveKey,
+}
+
+impl Default for SaveManager {
+    fn default() -> Self {
+        SaveManager {
+            directory: None,
+            key: SaveKey::Path,
+        }
+    }
+}
+
+impl SaveManager {
+    pub fn new(directory: Option<PathBuf>, key: SaveKey) -> Self {
+        SaveManager { directory, key }
+    }
+
+    pub fn path(&self, rom: &Path, header: &Header) -> PathBuf {
+        let name = match self.key {
+            SaveKey::Path => rom
+                .file_stem()
+                .map_or_else(|| header.title.get(), |stem| stem.to_string_lossy().into()),
+            SaveKey::Checksum => format!("{}-{:04X}", header.title.get(), header.global_checksum),
+        };
+        let directory = match self.directory {
+            Some(ref directory) => directory.as_path(),
+            None => rom.parent().unwrap_or_else(|| Path::new("")),
+        };
+        directory.join(format!("{}.sav", name))
+    }
+
+    pub fn open(&self, rom: &Path, header: &Header) -> SaveFile {
+        SaveFile::new(self.path(rom, header))
+    }
+
+    pub fn slots(&self, rom: &Path, header: &Header) -> Slots {
+        Slots(self.path(rom, header))
+    }
+}
+
+/// Numbered save state slots of a ROM, `<save name>.ss<slot>` next to its battery save
+#[derive(Debug, Clone)]
+pub struct Slots(PathBuf);
+
+impl Slots {
+    pub fn path(&self, slot: u8) -> PathBuf {
+        self.0.with_extension(format!("ss{}", slot))
+    }
+}
+
+/// Save file of the running cartridge, only written back when the RAM changed
+#[derive(Debug)]
+pub struct SaveFile {
+    path: PathBuf,
+    /// Content of the file on disk
+    flushed: Vec<u8>,
+    countdown: usize,
+}
+
+impl SaveFile {
+    pub fn new(path: PathBuf) -> Self {
+        SaveFile {
+            path,
+            flushed: Vec::new(),
+            countdown: FLUSH_INTERVAL,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Content of the save, `None` when the game was never saved
+    pub fn load(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(data) => {
+                self.flushed = data.clone();
+                Ok(Some(data))
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Count a T-cycle, true when the RAM should be flushed
+    pub fn tick(&mut self) -> bool {
+        self.countdown -= 1;
+        if self.countdown == 0 {
+            self.countdown = FLUSH_INTERVAL;
+            return true;
+        }
+        false
+    }
+
+    /// Write the data if it changed since the last flush. The file is replaced
+    /// at once so a crash never leaves a truncated save behind.
+    pub fn flush(&mut self, data: &[u8]) -> io::Result<()> {
+        if data == self.flushed.as_slice() {
+            return Ok(());
+        }
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let tmp = self.path.with_extension("sav.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)?;
+        self.flushed = data.to_vec();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_save {
+    use super::{SaveFile, SaveKey, SaveManager, FLUSH_INTERVAL};
+    use crate::Header;
+    use std::path::{Path, PathBuf};
+
+    fn header() -> Header {
+        let mut raw = vec![0; 0x50];
+        raw[0x34..0x39].copy_from_slice(b"TETRA");
+        raw[0x4e] = 0xbe;
+        raw[0x4f] = 0xef;
+        Header::try_from(raw).unwrap()
+    }
+
+    #[test]
+    fn test_save_path_by_rom_path() {
+        let saves = SaveManager::default();
+        let path = saves.path(Path::new("roms/tetra.gb"), &header());
+        assert_eq!(path, PathBuf::from("roms/tetra.sav"));
+
+        let saves = SaveManager::new(Some(PathBuf::from("saves")), SaveKey::Path);
+        let path = saves.path(Path::new("roms/tetra.gb"), &header());
+        assert_eq!(path, PathBuf::from("saves/tetra.sav"));
+    }
+
+    #[test]
+    fn test_save_path_by_checksum() {
+        let saves = SaveManager::new(Some(PathBuf::from("saves")), SaveKey::Checksum);
+        let path = saves.path(Path::new("roms/tetra.gb"), &header());
+        assert_eq!(path, PathBuf::from("saves/TETRA-BEEF.sav"));
+    }
+
+    #[test]
+    fn test_state_slots() {
+        let slots = SaveManager::default().slots(Path::new("roms/tetra.v1.gb"), &header());
+        assert_eq!(slots.path(3), PathBuf::from("roms/tetra.v1.ss3"));
+    }
+
+    #[test]
+    fn test_save_flush_only_changes() {
+        let directory = std::env::temp_dir().join("gbmu_test_save_flush");
+        let _ = std::fs::remove_dir_all(&directory);
+        let mut save = SaveFile::new(directory.join("game.sav"));
+        assert_eq!(save.load().unwrap(), None);
+
+        save.flush(&[1, 2, 3]).unwrap();
+        assert_eq!(std::fs::read(save.path()).unwrap(), vec![1, 2, 3]);
+        std::fs::remove_file(save.path()).unwrap();
+        save.flush(&[1, 2, 3]).unwrap();
+        assert!(!save.path().exists());
+
+        let mut reopened = SaveFile::new(directory.join("game.sav"));
+        save.flush(&[4]).unwrap();
+        assert_eq!(reopened.load().unwrap(), Some(vec![4]));
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn test_save_tick() {
+        let mut save = SaveFile::new(PathBuf::from("game.sav"));
+        assert_eq!((1..FLUSH_INTERVAL).filter(|_| save.tick()).count(), 0);
+        assert!(save.tick());
+        assert!(!save.tick());
+    }
+}
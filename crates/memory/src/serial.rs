@@ -1,4 +1,6 @@
 use crate::consts;
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
 
 #[derive(Debug, Default)]
 pub struct Serial {
@@ -23,3 +25,16 @@ impl Serial {
         }
     }
 }
+
+impl Snapshot for Serial {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.u8(self.data);
+        writer.u8(self.control);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.data = reader.u8()?;
+        self.control = reader.u8()?;
+        Ok(())
+    }
+}
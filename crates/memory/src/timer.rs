@@ -1,4 +1,6 @@
 use crate::consts;
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
 use shared::{Interrupt, Interrupts};
 
 #[derive(Debug)]
@@ -90,3 +92,26 @@ impl Timer {
         }
     }
 }
+
+impl Snapshot for Timer {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.u8(self.div);
+        writer.u8(self.tima);
+        writer.u8(self.tma);
+        writer.bool(self.enabled);
+        writer.u32(self.step);
+        writer.u32(self.internal_count);
+        writer.u32(self.internal_divider);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.div = reader.u8()?;
+        self.tima = reader.u8()?;
+        self.tma = reader.u8()?;
+        self.enabled = reader.bool()?;
+        self.step = reader.u32()?;
+        self.internal_count = reader.u32()?;
+        self.internal_divider = reader.u32()?;
+        Ok(())
+    }
+}
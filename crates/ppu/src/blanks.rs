@@ -4,43 +4,78 @@ use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-// time in cycles for rendering vblank
-const VBLANK: u16 = 4560; // 4,560 cycles for vblank
+// time in cycles for rendering a line, whatever the modes in it
+pub const LINE: u32 = 456; // 10 lines, 4,560 cycles for vblank
+const FRAME: u32 = LINE * 154; // 70,224 cycles for a frame
 
+/// Wait in HBlank or VBlank until the end of the line, on the clock of the
+/// runner so HBlank fills whatever the pixel transfer left
 pub struct Blank {
-    mode: Mode,
-    ticks: u16,
+    ppu: Ppu,
+    until: u32,
 }
 
 impl Future for Blank {
     type Output = u16;
 
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let dots = self.ppu.borrow().dots;
+        if dots >= self.until {
+            Poll::Ready(dots as u16)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Blank {
+    pub fn hblank(ppu: Ppu) -> Self {
+        Self::new(ppu, Mode::Hblank)
+    }
+
+    pub fn vblank(ppu: Ppu) -> Self {
+        Self::new(ppu, |_| Mode::Vblank)
+    }
+
+    /// `mode` gets the cycles left in the line
+    fn new(ppu: Ppu, mode: impl FnOnce(u16) -> Mode) -> Self {
+        let dots = ppu.borrow().dots;
+        let until = (dots / LINE + 1) * LINE;
+        ppu.borrow_mut().set_mode(mode((until - dots) as u16));
+        Self { ppu, until }
+    }
+
+    /// Only wait for the `duration` first cycles of the blank
+    pub fn cycles(self, duration: u32) -> Self {
+        let until = self.ppu.borrow().dots + duration;
+        Self { until, ..self }
+    }
+}
+
+/// Wait with the LCD off until it is turned on again, or for the time of a
+/// frame. Tells if the LCD was turned on
+pub struct Off {
+    ppu: Ppu,
+    ticks: u32,
+}
+
+impl Future for Off {
+    type Output = bool;
+
     fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
         self.ticks += 1;
-        match self.mode {
-            Mode::Hblank(ticks) => {
-                if self.ticks == ticks {
-                    Poll::Ready(self.ticks)
-                } else {
-                    Poll::Pending
-                }
-            }
-            Mode::Vblank => {
-                if self.ticks == VBLANK {
-                    Poll::Ready(self.ticks)
-                } else {
-                    Poll::Pending
-                }
-            }
-            _ => unreachable!(),
+        if self.ppu.borrow().registers.control.lcd_enabled {
+            Poll::Ready(true)
+        } else if self.ticks == FRAME {
+            Poll::Ready(false)
+        } else {
+            Poll::Pending
         }
     }
 }
 
-impl Blank {
-    pub fn new(ppu: Ppu, mode: Mode) -> Self {
-        let ticks = 0;
-        ppu.borrow_mut().registers.mode.update(mode);
-        Self { ticks, mode }
+impl Off {
+    pub fn new(ppu: Ppu) -> Self {
+        Self { ppu, ticks: 0 }
     }
 }
@@ -1,15 +1,18 @@
+use shared::snapshot::{Reader, Snapshot, Writer};
 use shared::Error;
 use std::collections::VecDeque;
 
 #[derive(Debug)]
 pub struct Fifo {
     queue: VecDeque<u8>,
+    /// Pixels dropped instead of poped, left of the screen
+    discard: u8,
 }
 
 impl<'push, 'fetch> Fifo {
     pub fn new() -> Self {
         let queue = VecDeque::with_capacity(16);
-        Self { queue }
+        Self { queue, discard: 0 }
     }
 
     pub fn try_push(&mut self, data: &[u8; 8]) -> Result<(), Error> {
@@ -27,16 +30,115 @@ impl<'push, 'fetch> Fifo {
     }
 
     pub fn clear(&mut self) {
-        self.queue.clear()
+        self.queue.clear();
+        self.discard = 0;
+    }
+
+    /// Drop the `count` next pixels, one per cycle as if they were poped
+    pub fn discard(&mut self, count: u8) {
+        self.discard = count;
     }
 
     pub fn try_pop(&mut self) -> Option<u8> {
         let len = self.queue.len();
         //println!("[FIFO] State: {:?}", self.queue);
-        if len > 8 {
+        if len > 8 && self.discard > 0 {
+            self.discard -= 1;
+            self.queue.pop_front().and(None)
+        } else if len > 8 {
             self.queue.pop_front()
         } else {
             None
         }
     }
 }
+
+/// A pixel of a sprite, mixed with the background when poped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Object {
+    pub color: u8,
+    /// Uses OBP1 instead of OBP0
+    pub palette: bool,
+    /// Drawn behind the background colors 1-3
+    pub behind: bool,
+}
+
+/// The sprite pixels, the front of the queue is on top of the next
+/// background pixel poped
+#[derive(Debug, Default)]
+pub struct SpriteFifo {
+    queue: VecDeque<Object>,
+}
+
+impl SpriteFifo {
+    /// Mix a row of sprite pixels starting at the next pixel. The pixels
+    /// already there were fetched first, so they win unless transparent
+    pub fn merge(&mut self, pixels: &[Object]) {
+        for (index, pixel) in pixels.iter().enumerate() {
+            match self.queue.get_mut(index) {
+                Some(current) if current.color == 0 => *current = *pixel,
+                Some(_) => {}
+                None => self.queue.push_back(*pixel),
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear()
+    }
+
+    pub fn pop(&mut self) -> Option<Object> {
+        self.queue.pop_front()
+    }
+}
+
+impl Snapshot for Fifo {
+    fn snapshot(&self, writer: &mut Writer) {
+        let queue: Vec<u8> = self.queue.iter().copied().collect();
+        writer.bytes(&queue);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.queue = reader.bytes()?.into();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_sprite_fifo {
+    use super::{Object, SpriteFifo};
+
+    fn row(colors: &[u8], palette: bool) -> Vec<Object> {
+        colors
+            .iter()
+            .map(|color| Object {
+                color: *color,
+                palette,
+                behind: false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_keeps_first_opaque_pixels() {
+        let mut fifo = SpriteFifo::default();
+
+        fifo.merge(&row(&[1, 0, 1, 0], false));
+        fifo.merge(&row(&[2, 2, 2, 2, 2, 2], true));
+
+        let popped: Vec<(u8, bool)> = std::iter::from_fn(|| fifo.pop())
+            .map(|pixel| (pixel.color, pixel.palette))
+            .collect();
+        assert_eq!(
+            popped,
+            vec![
+                (1, false),
+                (2, true),
+                (1, false),
+                (2, true),
+                (2, true),
+                (2, true)
+            ]
+        );
+    }
+}
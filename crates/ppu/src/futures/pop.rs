@@ -17,6 +17,11 @@ impl<'write> Pop<'write> {
         let poped = 0;
         Self { poped, ticks, ppu }
     }
+
+    /// The next pixel of the line
+    pub fn x(&self) -> usize {
+        self.poped
+    }
 }
 
 impl<'write> Future for Pop<'write> {
@@ -26,7 +31,8 @@ impl<'write> Future for Pop<'write> {
         let mut ppu = self.ppu.borrow_mut();
         self.ticks += 1;
         if let Some(pixel) = ppu.fifo.try_pop() {
-            ppu.output(self.poped, pixel);
+            let object = ppu.sprite_fifo.pop();
+            ppu.output(self.poped, pixel, object);
             self.poped += 1;
             //println!("[FIFO] Popped: {}", self.poped);
         }
@@ -8,6 +8,7 @@ pub mod ppu;
 pub mod registers;
 pub mod runner;
 pub(crate) mod transfert;
+pub(crate) mod window;
 
 pub use crate::interface::Ppu;
 pub use crate::registers::{Coordinates, Field, Registers};
@@ -2,12 +2,82 @@ use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-use crate::registers::Mode;
+use crate::registers::{Field, Mode};
 use crate::Ppu;
 
 const OAM_PERIOD: u16 = 80; // 77-83 cycles, 80 average
+const OAM_ENTRY_SIZE: usize = 4;
+/// Sprites drawn on a line at most, the next ones in OAM are ignored
+pub const SPRITES_PER_LINE: usize = 10;
 
-#[allow(dead_code)]
+/// An entry of the object attribute memory
+///
+/// Byte  Usage
+/// 0     Y position + 16
+/// 1     X position + 8
+/// 2     Tile index, always in 8000-8FFF
+/// 3     Attributes
+///         7  BG and Window over OBJ (0=No, 1=BG and Window colors 1-3 over the OBJ)
+///         6  Y flip
+///         5  X flip
+///         4  Palette (0=OBP0, 1=OBP1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sprite {
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub attributes: u8,
+}
+
+impl Sprite {
+    pub fn new(entry: &[u8]) -> Self {
+        Self {
+            y: entry[0],
+            x: entry[1],
+            tile: entry[2],
+            attributes: entry[3],
+        }
+    }
+
+    pub fn behind_background(&self) -> bool {
+        self.attributes & 0x80 == 0x80
+    }
+
+    pub fn y_flip(&self) -> bool {
+        self.attributes & 0x40 == 0x40
+    }
+
+    pub fn x_flip(&self) -> bool {
+        self.attributes & 0x20 == 0x20
+    }
+
+    /// Uses OBP1 instead of OBP0
+    pub fn palette(&self) -> bool {
+        self.attributes & 0x10 == 0x10
+    }
+
+    /// The sprite covers the line `ly`, for sprites `height` pixels high
+    pub fn on_line(&self, ly: u8, height: u8) -> bool {
+        let top = self.y as i16 - 16;
+        (top..top + height as i16).contains(&(ly as i16))
+    }
+
+    /// Address of the row of pixels drawn on the line `ly`, the lowest bit
+    /// of the tile index is ignored for 8x16 sprites
+    pub fn row_address(&self, ly: u8, height: u8) -> u16 {
+        let mut line = (ly as i16 + 16 - self.y as i16) as u16;
+        if self.y_flip() {
+            line = height as u16 - 1 - line;
+        }
+        let tile = match height {
+            16 => self.tile & 0xFE,
+            _ => self.tile,
+        };
+        0x8000 + tile as u16 * 16 + line * 2
+    }
+}
+
+/// Select the sprites of the line, one OAM entry is checked every 2 cycles
 pub struct Oam {
     ticks: u16,
     ppu: Ppu,
@@ -16,9 +86,26 @@ pub struct Oam {
 impl Oam {
     pub fn search(ppu: Ppu) -> Self {
         let ticks = 0;
-        ppu.borrow_mut().registers.mode.update(Mode::Oam);
+        {
+            let mut p = ppu.borrow_mut();
+            let p = &mut *p;
+            p.set_mode(Mode::Oam);
+            p.sprites.clear();
+            p.window.new_line(&p.registers);
+        }
         Self { ticks, ppu }
     }
+
+    fn check(&self, entry: usize) {
+        let mut ppu = self.ppu.borrow_mut();
+        let ly = ppu.registers.coordinates.get(Field::Ly);
+        let height = ppu.registers.control.sprite_size;
+        let start = entry * OAM_ENTRY_SIZE;
+        let sprite = Sprite::new(&ppu.oam()[start..start + OAM_ENTRY_SIZE]);
+        if ppu.sprites.len() < SPRITES_PER_LINE && sprite.on_line(ly, height) {
+            ppu.sprites.push(sprite);
+        }
+    }
 }
 
 impl Future for Oam {
@@ -26,6 +113,9 @@ impl Future for Oam {
 
     fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
         self.ticks += 1;
+        if self.ticks.is_multiple_of(2) {
+            self.check(self.ticks as usize / 2 - 1);
+        }
         if self.ticks == OAM_PERIOD {
             Poll::Ready(self.ticks)
         } else {
@@ -33,3 +123,68 @@ impl Future for Oam {
         }
     }
 }
+
+#[cfg(test)]
+mod test_oam {
+    use super::{Oam, Sprite};
+    use crate::registers::Field;
+    use crate::Ppu;
+    use shared::{execute, Interrupts};
+
+    fn setup_ppu(ly: u8, sprites: &[(u8, u8)]) -> Ppu {
+        let ppu = Ppu::new(Interrupts::default(), false);
+        let mut p = ppu.borrow_mut();
+        for (index, (y, x)) in sprites.iter().enumerate() {
+            p.set_oam(0xFE00 + index as u16 * 4, *y).unwrap();
+            p.set_oam(0xFE01 + index as u16 * 4, *x).unwrap();
+            p.set_oam(0xFE02 + index as u16 * 4, index as u8).unwrap();
+        }
+        p.registers.coordinates.set(Field::Ly, ly);
+        drop(p);
+        ppu
+    }
+
+    fn search(ppu: &Ppu) -> Vec<u8> {
+        execute::execute(Box::pin(Oam::search(ppu.clone())));
+        ppu.borrow()
+            .sprites
+            .iter()
+            .map(|sprite| sprite.tile)
+            .collect()
+    }
+
+    #[test]
+    fn test_search_selects_sprites_on_line() {
+        let ppu = setup_ppu(10, &[(20, 8), (26, 8), (27, 8), (19, 0), (3, 8)]);
+
+        assert_eq!(search(&ppu), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn test_search_keeps_ten_sprites() {
+        let ppu = setup_ppu(0, &[(16, 8); 12]);
+
+        assert_eq!(search(&ppu), (0..10).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_search_tall_sprites() {
+        let ppu = setup_ppu(10, &[(16, 8), (8, 8)]);
+        assert_eq!(search(&ppu), vec![]);
+
+        ppu.borrow_mut().registers.set(0xFF40, 0x95);
+        assert_eq!(search(&ppu), vec![0]);
+    }
+
+    #[test]
+    fn test_row_address() {
+        let sprite = Sprite::new(&[16, 8, 0x03, 0x00]);
+        assert_eq!(sprite.row_address(0, 8), 0x8030);
+        assert_eq!(sprite.row_address(15, 16), 0x803E);
+
+        let flipped = Sprite::new(&[16, 8, 0x03, 0x40]);
+        assert_eq!(flipped.row_address(0, 8), 0x803E);
+        assert_eq!(flipped.row_address(0, 16), 0x803E);
+        assert_eq!(flipped.row_address(15, 16), 0x8020);
+    }
+}
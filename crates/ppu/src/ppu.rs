@@ -1,6 +1,9 @@
 use crate::colors::Color;
-use crate::fifo::Fifo;
-use crate::registers::{Mode, Registers};
+use crate::fifo::{Fifo, Object, SpriteFifo};
+use crate::oam::Sprite;
+use crate::registers::{Field, Mode, Registers};
+use crate::window::Window;
+use shared::snapshot::{Reader, Snapshot, Writer};
 use shared::Interrupts;
 use shared::{Error, Interrupt};
 
@@ -19,6 +22,16 @@ pub struct Ppu {
     pub vram_lock: bool,
     pub registers: Registers,
     pub(crate) fifo: Fifo,
+    /// Sprites of the current line not fetched yet, in OAM order
+    pub(crate) sprites: Vec<Sprite>,
+    pub(crate) sprite_fifo: SpriteFifo,
+    pub(crate) window: Window,
+    /// Last state of the STAT interrupt line, see `Ppu::update_stat`
+    stat_line: bool,
+    /// Cycles since the start of the line, see `runner::Runner`
+    pub(crate) dots: u32,
+    /// The first frame after the LCD is turned on is not shown
+    pub(crate) blank_frame: bool,
 }
 
 impl AsRef<Vec<u8>> for Ppu {
@@ -42,7 +55,7 @@ impl Ppu {
             false => Registers::new(),
         };
         let fifo = Fifo::new();
-        let screen = vec![Color::Black; FRAME_WIDTH * FRAME_HEIGHT];
+        let screen = vec![Color::White; FRAME_WIDTH * FRAME_HEIGHT];
         let oam = vec![0; OAM_TABLE];
         Self {
             vram_lock: false,
@@ -51,6 +64,12 @@ impl Ppu {
             registers,
             interrupts,
             fifo,
+            sprites: Vec::with_capacity(crate::oam::SPRITES_PER_LINE),
+            sprite_fifo: SpriteFifo::default(),
+            window: Window::default(),
+            stat_line: false,
+            dots: 0,
+            blank_frame: false,
             screen,
         }
     }
@@ -65,6 +84,10 @@ impl Ppu {
         Ok(self.oam[address])
     }
 
+    pub(crate) fn oam(&self) -> &[u8] {
+        &self.oam
+    }
+
     pub fn set_vram(&mut self, address: u16, data: u8) -> Result<(), Error> {
         let address: usize = (address - VRAM_START) as usize;
         self.vram[address] = data;
@@ -78,6 +101,16 @@ impl Ppu {
         Ok(())
     }
 
+    /// The CPU can't reach VRAM while the pixels are transferred
+    pub fn vram_locked(&self) -> bool {
+        self.registers.mode == Mode::Transfert
+    }
+
+    /// Nor OAM while it is scanned and the pixels are transferred
+    pub fn oam_locked(&self) -> bool {
+        matches!(self.registers.mode, Mode::Oam | Mode::Transfert)
+    }
+
     pub fn get_registers(&self, address: u16) -> Result<u8, Error> {
         Ok(self.registers.get(address))
     }
@@ -87,12 +120,30 @@ impl Ppu {
         //     "CPU is Writing to PPU Registers at {:#X}, data: {:#b}",
         //     address, data
         // );
+        let lcd = self.registers.control.lcd_enabled;
         self.registers.set(address, data);
+        match (lcd, self.registers.control.lcd_enabled) {
+            (true, false) => self.lcd_off(),
+            (false, true) => self.blank_frame = true,
+            _ => {}
+        }
+        self.update_stat();
         Ok(())
     }
 
+    /// LY and the mode are reset by the registers, the line being drawn is
+    /// dropped, VRAM and OAM are free and the screen goes white
+    fn lcd_off(&mut self) {
+        self.vram_lock = false;
+        self.fifo.clear();
+        self.sprite_fifo.clear();
+        self.sprites.clear();
+        self.window.new_frame();
+        self.screen.fill(Color::White);
+    }
+
     pub fn render(&mut self, frame: &mut [u8]) {
-        if self.registers().mode == Mode::Vblank {
+        if self.registers().mode == Mode::Vblank || !self.registers.control.lcd_enabled {
             //println!("[PPU] Outputing to screen");
             for (index, pixel) in frame.chunks_exact_mut(4).enumerate() {
                 let to_display: [u8; 4] = self.screen[index].into();
@@ -102,9 +153,66 @@ impl Ppu {
         }
     }
 
-    pub fn output(&mut self, x: usize, pixel: u8) {
+    /// The screen as RGBA whatever the mode, outside of VBlank the lines not
+    /// drawn yet still hold the previous frame
+    pub fn rgba(&self) -> Vec<u8> {
+        self.screen
+            .iter()
+            .flat_map(|color| <[u8; 4]>::from(*color))
+            .collect()
+    }
+
+    /// The screen as shades from 0 (white) to 3 (black), whatever the mode
+    pub fn shades(&self) -> Vec<u8> {
+        self.screen.iter().map(|color| u8::from(*color)).collect()
+    }
+
+    /// Take the next sprite starting on the pixel `x` of the line. Sprites
+    /// partially left of the screen all start on 0, the lowest x is taken
+    /// first, then the lowest OAM index
+    pub(crate) fn next_sprite(&mut self, x: usize) -> Option<Sprite> {
+        if !self.registers.control.sprite_enabled {
+            return None;
+        }
+        let (index, _) = self
+            .sprites
+            .iter()
+            .enumerate()
+            .filter(|(_, sprite)| (sprite.x as usize).saturating_sub(8) == x)
+            .min_by_key(|(_, sprite)| sprite.x)?;
+        Some(self.sprites.remove(index))
+    }
+
+    /// Start the window if it begins on the pixel `x` of the line, see
+    /// `Window::start`
+    pub(crate) fn start_window(&mut self, x: usize) -> Option<u8> {
+        self.window.start(&self.registers, x)
+    }
+
+    /// Mix a background pixel with the sprite pixel on top of it.
+    /// Transparent sprite pixels show the background, as do sprites behind
+    /// the background unless its color is 0
+    pub fn output(&mut self, x: usize, pixel: u8, object: Option<Object>) {
+        if self.blank_frame {
+            return;
+        }
         let offset = self.registers.coordinates.offset(x);
-        let color = self.registers.bgp.color(pixel);
+        let pixel = match self.registers.control.priority {
+            true => Some(pixel),
+            false => None,
+        };
+        let color = match (pixel, object) {
+            (pixel, Some(object))
+                if object.color != 0 && !(object.behind && pixel.unwrap_or(0) != 0) =>
+            {
+                match object.palette {
+                    false => self.registers.objp0.color(object.color),
+                    true => self.registers.objp1.color(object.color),
+                }
+            }
+            (Some(pixel), _) => self.registers.bgp.color(pixel),
+            (None, _) => Color::White,
+        };
         //println!("[PPU] position Offset: {}", offset);
         // println!(
         //     "[FIFO] Poped data. offset: {}, len {}",
@@ -121,25 +229,33 @@ impl Ppu {
         self.registers.coordinates.update(coordinates)
     }
 
-    pub fn raise_ly_lyc(&self) {
-        if self.registers().lyc_ly_interupt && self.registers().lyc_ly {
-            self.raise_lcd();
-        }
+    pub(crate) fn set_mode(&mut self, mode: Mode) {
+        self.registers.mode.update(mode);
+        self.update_stat();
     }
 
-    pub fn raise_hblank(&self) {
-        self.raise_lcd();
-        if self.registers().hblank_interupt {}
+    pub(crate) fn next_line(&mut self) {
+        self.registers.increase(Field::Ly);
+        self.update_stat();
     }
 
-    pub fn raise_oam(&self) {
-        self.raise_lcd();
-        if self.registers().oam_interupt {}
+    pub(crate) fn clear_line(&mut self) {
+        self.registers.clear(Field::Ly);
+        self.update_stat();
+    }
+
+    /// The STAT interrupt is only requested on a rising edge of its line: a
+    /// source going up while another one already holds the line is blocked
+    pub fn update_stat(&mut self) {
+        let line = self.registers.stat_line();
+        if line && !self.stat_line {
+            self.raise_lcd();
+        }
+        self.stat_line = line;
     }
 
     pub fn raise_vblank(&self) {
         self.interrupts.borrow_mut().request(Interrupt::VBlank);
-        if self.registers().vblank_interupt {}
     }
 
     pub fn raise_lcd(&self) {
@@ -151,3 +267,105 @@ impl Ppu {
         &self.registers
     }
 }
+
+impl Snapshot for Ppu {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.bytes(&self.vram);
+        writer.bytes(&self.oam);
+        writer.bytes(&self.shades());
+        writer.bool(self.vram_lock);
+        self.registers.snapshot(writer);
+        self.fifo.snapshot(writer);
+        self.window.snapshot(writer);
+        writer.bool(self.stat_line);
+        writer.bool(self.blank_frame);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        reader.bytes_into(&mut self.vram)?;
+        reader.bytes_into(&mut self.oam)?;
+        let mut screen = vec![0; self.screen.len()];
+        reader.bytes_into(&mut screen)?;
+        self.screen = screen.into_iter().map(Color::from).collect();
+        self.vram_lock = reader.bool()?;
+        self.registers.restore(reader)?;
+        self.fifo.restore(reader)?;
+        self.window.restore(reader)?;
+        self.stat_line = reader.bool()?;
+        self.blank_frame = reader.bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_stat {
+    use super::Ppu;
+    use crate::registers::{Field, Mode};
+    use shared::{Interrupt, Interrupts};
+
+    fn setup_ppu(stat: u8) -> (Ppu, Interrupts) {
+        let interrupts = Interrupts::default();
+        let mut ppu = Ppu::new(interrupts.clone(), false);
+        ppu.set_registers(0xFF45, 0x10).unwrap();
+        ppu.set_mode(Mode::Transfert);
+        ppu.set_registers(0xFF41, stat).unwrap();
+        (ppu, interrupts)
+    }
+
+    fn requested(interrupts: &Interrupts) -> bool {
+        let requested = interrupts.borrow().status(Interrupt::Lcd);
+        interrupts.borrow_mut().processed(Interrupt::Lcd);
+        requested
+    }
+
+    #[test]
+    fn test_stat_sources_gated() {
+        let (mut ppu, interrupts) = setup_ppu(0x20);
+
+        ppu.set_mode(Mode::Hblank(204));
+        assert!(!requested(&interrupts));
+        ppu.set_mode(Mode::Oam);
+        assert!(requested(&interrupts));
+    }
+
+    #[test]
+    fn test_stat_rising_edge_only() {
+        let (mut ppu, interrupts) = setup_ppu(0x08);
+
+        ppu.set_mode(Mode::Hblank(204));
+        assert!(requested(&interrupts));
+        ppu.set_mode(Mode::Hblank(204));
+        assert!(!requested(&interrupts));
+    }
+
+    #[test]
+    fn test_stat_blocking() {
+        let (mut ppu, interrupts) = setup_ppu(0x48);
+        ppu.registers.coordinates.set(Field::Ly, 0x0F);
+
+        ppu.set_mode(Mode::Hblank(204));
+        assert!(requested(&interrupts));
+        ppu.next_line();
+        assert!(!requested(&interrupts));
+        ppu.set_mode(Mode::Oam);
+        assert!(!requested(&interrupts));
+    }
+
+    #[test]
+    fn test_stat_lyc_write() {
+        let (mut ppu, interrupts) = setup_ppu(0x40);
+
+        ppu.set_registers(0xFF45, 0x00).unwrap();
+        assert!(requested(&interrupts));
+        assert_eq!(ppu.get_registers(0xFF41).unwrap() & 0x04, 0x04);
+    }
+
+    #[test]
+    fn test_stat_vblank_oam_source() {
+        let (mut ppu, interrupts) = setup_ppu(0x20);
+        ppu.registers.coordinates.set(Field::Ly, 144);
+
+        ppu.set_mode(Mode::Vblank);
+        assert!(requested(&interrupts));
+    }
+}
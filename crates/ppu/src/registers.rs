@@ -3,7 +3,10 @@ pub mod control;
 pub mod coordinates;
 
 pub use coordinates::{Coordinates, Field};
+use enum_iterator::IntoEnumIterator;
 use num_enum::TryFromPrimitive;
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
 mod palette;
 
 pub use control::Control;
@@ -77,8 +80,8 @@ pub struct Registers {
     //Lcd Coordinates
     pub coordinates: Coordinates,
     pub bgp: palette::Monochrome,
-    // objp0: palette::Monochrome,
-    // objp1: palette::Monochrome,
+    pub objp0: palette::Monochrome,
+    pub objp1: palette::Monochrome,
     // bcps: palette::Index,
     // bcpd: palette::Data,
     // ocps: palette::Index,
@@ -132,6 +135,24 @@ impl Registers {
         self.coordinates.is_lower(field, data)
     }
 
+    /// The STAT interrupt line, the OR of the sources gated by their enable
+    /// bits. The OAM source also holds it on the first line of VBlank
+    pub fn stat_line(&self) -> bool {
+        if !self.control.lcd_enabled {
+            return false;
+        }
+        let mode = match self.mode {
+            Mode::Hblank(_) => self.hblank_interupt,
+            Mode::Vblank => {
+                self.vblank_interupt
+                    || (self.oam_interupt && self.coordinates.get(Field::Ly) == 144)
+            }
+            Mode::Oam => self.oam_interupt,
+            Mode::Transfert => false,
+        };
+        mode || (self.lyc_ly_interupt && self.lyc_ly)
+    }
+
     pub fn tile_map_row_address(&self) -> u16 {
         self.control.bg_area + self.coordinates.map_row_offset()
     }
@@ -145,11 +166,7 @@ impl Registers {
                 byte |= if self.oam_interupt { 0x20 } else { 0 };
                 byte |= if self.vblank_interupt { 0x10 } else { 0 };
                 byte |= if self.hblank_interupt { 0x08 } else { 0 };
-                byte |= if self.coordinates.ly_compare() {
-                    0x04
-                } else {
-                    0
-                };
+                byte |= if self.lyc_ly { 0x04 } else { 0 };
                 byte |= self.mode.get();
                 byte
             }
@@ -158,6 +175,8 @@ impl Registers {
                 self.coordinates.get(field)
             }
             0xFF47 => self.bgp.get(),
+            0xFF48 => self.objp0.get(),
+            0xFF49 => self.objp1.get(),
             _ => unreachable!(),
         }
     }
@@ -169,6 +188,7 @@ impl Registers {
                 self.control.set(data);
                 if old_lcd && !self.control.lcd_enabled {
                     self.coordinates.set(Field::Ly, 0);
+                    self.check_ly();
                     self.mode = Mode::Hblank(456);
                     // Clear screen and reset clock
                 }
@@ -182,15 +202,60 @@ impl Registers {
             0xFF42..=0xFF45 | 0xFF4A | 0xFF4B => {
                 let field = Field::try_from_primitive(address).unwrap();
                 self.coordinates.set(field, data);
+                self.check_ly();
             }
             0xFF47 => {
                 self.bgp.set(data);
             }
+            0xFF48 => {
+                self.objp0.set(data);
+            }
+            0xFF49 => {
+                self.objp1.set(data);
+            }
             _ => unreachable!(),
         }
     }
 }
 
+impl Snapshot for Registers {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.u8(self.control.get());
+        writer.u8(self.get(0xFF41));
+        writer.bool(self.lyc_ly);
+        writer.u8(self.mode.get());
+        if let Mode::Hblank(ticks) = self.mode {
+            writer.u16(ticks);
+        }
+        for field in Field::into_enum_iter() {
+            writer.u8(self.coordinates.get(field));
+        }
+        writer.u8(self.bgp.get());
+        writer.u8(self.objp0.get());
+        writer.u8(self.objp1.get());
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.control.set(reader.u8()?);
+        self.set(0xFF41, reader.u8()?);
+        self.lyc_ly = reader.bool()?;
+        self.mode = match reader.u8()? {
+            0 => Mode::Hblank(reader.u16()?),
+            1 => Mode::Vblank,
+            2 => Mode::Oam,
+            3 => Mode::Transfert,
+            _ => return Err(Error::InvalidState),
+        };
+        for field in Field::into_enum_iter() {
+            self.coordinates.set(field, reader.u8()?);
+        }
+        self.bgp.set(reader.u8()?);
+        self.objp0.set(reader.u8()?);
+        self.objp1.set(reader.u8()?);
+        Ok(())
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Mode {
     Hblank(u16),
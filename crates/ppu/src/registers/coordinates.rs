@@ -2,7 +2,7 @@ use enum_iterator::IntoEnumIterator;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use std::fmt;
 
-const MAP_ROW_LEN: u16 = 32;
+pub const MAP_ROW_LEN: u16 = 32;
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct Coordinates {
     yscroll: u8,
@@ -74,8 +74,14 @@ impl Coordinates {
         *dst = Self { ..*self };
     }
 
-    pub fn x_range(&self) -> XRange {
-        XRange::new(self.xscroll)
+    /// Column in the background map of the `x`th tile fetched on the line
+    pub fn map_column(&self, x: u8) -> u16 {
+        ((self.xscroll / 8).wrapping_add(x) as u16) % MAP_ROW_LEN
+    }
+
+    /// Background pixels left of the screen, dropped at the start of the line
+    pub fn fine_scroll(&self) -> u8 {
+        self.xscroll & 0x07
     }
 
     pub fn y(&self) -> usize {
@@ -1,7 +1,6 @@
-use crate::blanks::Blank;
+use crate::blanks::{Blank, Off};
 use crate::oam::Oam;
 use crate::registers as lcd;
-use crate::registers::Mode;
 use crate::transfert::Pixel;
 use crate::Ppu;
 use shared::{Error, Finished, Output, Run};
@@ -10,48 +9,178 @@ use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-pub struct Runner<T> {
-    inner: Pin<Box<dyn Future<Output = T>>>,
+/// Cycles of the line 153 before LY reads 0
+const LY_153_CYCLES: u32 = 4;
+
+/// Each poll of the runner is a cycle, counted in `Ppu::dots` from the
+/// start of the line (or of VBlank) it runs
+pub struct Runner {
+    ppu: Ppu,
+    /// The LCD was on when the runner started, turning it off stops the line
+    lcd: bool,
+    inner: Output,
 }
 
-impl<T> Runner<T> {
-    pub fn new(inner: Pin<Box<dyn Future<Output = T>>>) -> Self {
-        Self { inner }
+impl Runner {
+    pub fn new(ppu: Ppu, inner: Output) -> Self {
+        ppu.borrow_mut().dots = 0;
+        let lcd = ppu.borrow().registers.control.lcd_enabled;
+        Self { ppu, lcd, inner }
     }
 }
 
-impl<T> Future for Runner<T> {
-    type Output = T;
+impl Future for Runner {
+    type Output = Result<Finished, Error>;
 
     fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        self.ppu.borrow_mut().dots += 1;
+        if self.lcd && !self.ppu.borrow().registers.control.lcd_enabled {
+            return Poll::Ready(Ok(Finished::Nope));
+        }
         self.inner.as_mut().poll(context)
     }
 }
 
 impl Run for Ppu {
     fn run(self) -> Output {
-        let inner = Box::pin(run(self));
-        Box::pin(Runner::new(inner))
+        let inner = Box::pin(run(self.clone()));
+        Box::pin(Runner::new(self, inner))
     }
 }
 
 async fn run(ppu: Ppu) -> Result<Finished, Error> {
     if !ppu.borrow().registers.control.lcd_enabled {
-        Ok(Finished::Nope)
+        // Nothing is drawn, the white screen still goes out once per frame
+        match Off::new(ppu.clone()).await {
+            true => Ok(Finished::Nope),
+            false => Ok(Finished::Frame),
+        }
     } else if ppu.borrow_mut().registers.is_equal(lcd::Field::Ly, 144) {
         ppu.borrow().raise_vblank();
-        ppu.borrow_mut().registers.mode.update(Mode::Vblank);
-        Blank::new(ppu.clone(), Mode::Vblank).await;
-        ppu.borrow_mut().registers.clear(lcd::Field::Ly);
+        while ppu.borrow_mut().registers.is_lower(lcd::Field::Ly, 153) {
+            Blank::vblank(ppu.clone()).await;
+            ppu.borrow_mut().next_line();
+        }
+        // LY already reads 0 a few cycles into the last line
+        Blank::vblank(ppu.clone()).cycles(LY_153_CYCLES).await;
+        ppu.borrow_mut().clear_line();
+        Blank::vblank(ppu.clone()).await;
+        ppu.borrow_mut().window.new_frame();
+        ppu.borrow_mut().blank_frame = false;
         Ok(Finished::Frame)
     } else {
-        ppu.borrow().raise_oam();
-        let mut ticks = Oam::search(ppu.clone()).await;
-        ticks += Pixel::transfert(ppu.clone()).start().await?;
-        ppu.borrow_mut().registers.increase(lcd::Field::Ly);
-        ppu.borrow().raise_ly_lyc();
-        ppu.borrow().raise_hblank();
-        ticks += Blank::new(ppu.clone(), Mode::Hblank(204)).await;
+        Oam::search(ppu.clone()).await;
+        Pixel::transfert(ppu.clone()).start().await?;
+        let ticks = Blank::hblank(ppu.clone()).await;
+        ppu.borrow_mut().next_line();
         Ok(Finished::Line(ticks))
     }
 }
+
+#[cfg(test)]
+mod test_runner {
+    use crate::registers::Field;
+    use crate::Ppu;
+    use shared::{waker, Finished, Interrupts, Run};
+    use std::task::{Context, Poll};
+
+    /// Cycles until the runner finishes, and the cycles spent in mode 3
+    fn run(ppu: &Ppu) -> (u32, u16, Finished) {
+        let waker = waker::create();
+        let mut context = Context::from_waker(&waker);
+        let mut runner = ppu.clone().run();
+        let (mut cycles, mut transfert) = (0, 0);
+        loop {
+            cycles += 1;
+            let poll = runner.as_mut().poll(&mut context);
+            if ppu.borrow().registers.get(0xFF41) & 0x03 == 0x03 {
+                transfert += 1;
+            }
+            if let Poll::Ready(finished) = poll {
+                break (cycles, transfert, finished.unwrap());
+            }
+        }
+    }
+
+    fn setup_ppu() -> Ppu {
+        let ppu = Ppu::new(Interrupts::default(), false);
+        ppu.borrow_mut().registers.set(0xFF40, 0x93);
+        ppu
+    }
+
+    #[test]
+    fn test_line_lasts_456_cycles() {
+        let ppu = setup_ppu();
+
+        let (cycles, transfert, finished) = run(&ppu);
+        assert_eq!(cycles, 456);
+        assert_eq!(transfert, 172);
+        assert!(matches!(finished, Finished::Line(456)));
+    }
+
+    #[test]
+    fn test_transfert_penalties() {
+        let ppu = setup_ppu();
+        ppu.borrow_mut().registers.set(0xFF43, 0x03);
+        ppu.borrow_mut().set_oam(0xFE00, 16).unwrap();
+        ppu.borrow_mut().set_oam(0xFE01, 8).unwrap();
+
+        let (cycles, transfert, _) = run(&ppu);
+        assert_eq!(cycles, 456);
+        assert_eq!(transfert, 172 + 3 + 6 + 2);
+    }
+
+    #[test]
+    fn test_vblank_lasts_10_lines() {
+        let ppu = setup_ppu();
+        ppu.borrow_mut().registers.coordinates.set(Field::Ly, 144);
+
+        let (cycles, _, finished) = run(&ppu);
+        assert_eq!(cycles, 4560);
+        assert!(matches!(finished, Finished::Frame));
+        assert_eq!(ppu.borrow().registers.coordinates.get(Field::Ly), 0);
+    }
+
+    #[test]
+    fn test_lcd_off_frame() {
+        let ppu = setup_ppu();
+        ppu.borrow_mut().set_registers(0xFF40, 0x13).unwrap();
+
+        let (cycles, _, finished) = run(&ppu);
+        assert_eq!(cycles, 70224);
+        assert!(matches!(finished, Finished::Frame));
+    }
+
+    #[test]
+    fn test_lcd_off_stops_the_line() {
+        let ppu = setup_ppu();
+        ppu.borrow_mut().registers.coordinates.set(Field::Ly, 10);
+        let waker = waker::create();
+        let mut context = Context::from_waker(&waker);
+        let mut runner = ppu.clone().run();
+        for _ in 0..100 {
+            assert!(runner.as_mut().poll(&mut context).is_pending());
+        }
+        assert!(ppu.borrow().vram_lock);
+
+        ppu.borrow_mut().set_registers(0xFF40, 0x13).unwrap();
+        let poll = runner.as_mut().poll(&mut context);
+        assert!(matches!(poll, Poll::Ready(Ok(Finished::Nope))));
+        let p = ppu.borrow();
+        assert_eq!(p.registers.coordinates.get(Field::Ly), 0);
+        assert_eq!(p.registers.get(0xFF41) & 0x03, 0);
+        assert!(!p.vram_lock);
+    }
+
+    #[test]
+    fn test_first_frame_after_lcd_on_is_blank() {
+        let ppu = Ppu::new(Interrupts::default(), true);
+        ppu.borrow_mut().set_registers(0xFF47, 0xFF).unwrap();
+        ppu.borrow_mut().set_registers(0xFF40, 0x93).unwrap();
+
+        while !matches!(run(&ppu).2, Finished::Frame) {}
+        assert!(ppu.borrow().shades().iter().all(|shade| *shade == 0));
+        while !matches!(run(&ppu).2, Finished::Frame) {}
+        assert!(ppu.borrow().shades().iter().all(|shade| *shade == 3));
+    }
+}
@@ -1,15 +1,21 @@
 mod fetcher;
 mod pixels;
+mod sprites;
+mod timing;
 use std::task::{Context, Poll};
 
 use futures::Future;
 
-use crate::{futures::Pop, registers::Mode};
+use crate::{
+    futures::Pop,
+    registers::{Field, Mode},
+};
 use futures::pending;
 use shared::Error;
 
 use crate::Ppu;
 use fetcher::Fetcher;
+use timing::Timing;
 
 #[allow(dead_code)]
 pub struct Pixel {
@@ -18,7 +24,7 @@ pub struct Pixel {
 
 impl Pixel {
     pub fn transfert(ppu: Ppu) -> Self {
-        ppu.borrow_mut().registers.mode.update(Mode::Transfert);
+        ppu.borrow_mut().set_mode(Mode::Transfert);
         Self { ppu }
     }
 
@@ -32,26 +38,204 @@ impl Pixel {
         let fetcher = Fetcher::new(self.ppu.clone()).fetch();
         let mut fetching = Box::pin(fetcher);
         let mut pop = Box::pin(Pop::new(&self.ppu));
+        let mut sprite = None;
+        let mut window = false;
+        let (start, mut timing) = {
+            let p = self.ppu.borrow();
+            (p.dots, Timing::new(p.registers.coordinates.xscroll()))
+        };
 
+        {
+            let mut p = self.ppu.borrow_mut();
+            let fine_scroll = p.registers.coordinates.fine_scroll();
+            p.fifo.clear();
+            p.fifo.discard(fine_scroll);
+            p.sprite_fifo.clear();
+        }
         self.ppu.borrow_mut().vram_lock = true;
-        let cycles = loop {
+        loop {
+            // The window restarts the fetcher on its own map, dropping the
+            // background pixels already in the fifo
+            if !window {
+                let start = self.ppu.borrow_mut().start_window(pop.x());
+                if let Some(hidden) = start {
+                    window = true;
+                    timing.window(self.ppu.borrow().registers.coordinates.get(Field::Xwindow));
+                    fetching = Box::pin(Fetcher::window(self.ppu.clone()).fetch());
+                    self.ppu.borrow_mut().fifo.clear();
+                    self.ppu.borrow_mut().fifo.discard(hidden);
+                }
+            }
+            // A sprite on the next pixel pauses the fetcher and the fifo
+            // while its row is fetched
+            if sprite.is_none() {
+                let next = self.ppu.borrow_mut().next_sprite(pop.x());
+                next.iter().for_each(|next| timing.sprite(next.x));
+                sprite = next.map(|next| Box::pin(sprites::fetch(&self.ppu, next)));
+            }
+            if let Some(fetching) = sprite.as_mut() {
+                if let Poll::Ready(result) = fetching.as_mut().poll(&mut context) {
+                    result?;
+                    sprite = None;
+                }
+                pending!();
+                continue;
+            }
             match fetching.as_mut().poll(&mut context) {
                 Poll::Ready(_) => {
-                    let fetcher = Fetcher::new(self.ppu.clone()).fetch();
-                    fetching = Box::pin(fetcher);
+                    let fetcher = match window {
+                        true => Fetcher::window(self.ppu.clone()),
+                        false => Fetcher::new(self.ppu.clone()),
+                    };
+                    fetching = Box::pin(fetcher.fetch());
                 }
                 Poll::Pending => (),
             }
-            match pop.as_mut().poll(&mut context) {
-                Poll::Ready(ticks) => {
-                    break ticks;
-                }
-                Poll::Pending => (),
+            if pop.as_mut().poll(&mut context).is_ready() {
+                break;
             }
             pending!();
-        };
+        }
+        // The line is done, but the transfer lasts as long as the hardware
+        // needs for it
+        while self.ppu.borrow().dots < start + timing.cycles() as u32 {
+            pending!();
+        }
         self.ppu.borrow_mut().vram_lock = false;
-        //println!("[FETCHER] fetcher ticks: {}", cycles);
-        Ok(cycles)
+        self.ppu.borrow_mut().window.end_line();
+        Ok((self.ppu.borrow().dots - start) as u16)
+    }
+}
+
+#[cfg(test)]
+mod test_transfert {
+    use super::Pixel;
+    use crate::runner::Runner;
+    use crate::Ppu;
+    use shared::{execute, Finished, Interrupts};
+
+    /// Tile 0 is the background, tiles 1 and 2 are filled with colors 1 and 2
+    fn setup_ppu(background: u8, sprites: &[[u8; 4]]) -> Ppu {
+        let ppu = Ppu::new(Interrupts::default(), false);
+        let mut p = ppu.borrow_mut();
+        p.registers.set(0xFF40, 0x93);
+        p.registers.set(0xFF48, 0xE4);
+        p.registers.set(0xFF49, 0xE4);
+        p.set_vram(0x8000, background).unwrap();
+        p.set_vram(0x8010, 0xFF).unwrap();
+        p.set_vram(0x8021, 0xFF).unwrap();
+        for (index, sprite) in sprites.iter().enumerate() {
+            for (offset, byte) in sprite.iter().enumerate() {
+                p.set_oam(0xFE00 + (index * 4 + offset) as u16, *byte)
+                    .unwrap();
+            }
+        }
+        p.sprites = sprites
+            .iter()
+            .map(|sprite| crate::oam::Sprite::new(sprite))
+            .collect();
+        drop(p);
+        ppu
+    }
+
+    fn line(ppu: &Ppu) -> Vec<u8> {
+        let transfert = Pixel::transfert(ppu.clone()).start();
+        let line = Box::pin(async move { transfert.await.map(Finished::Line) });
+        execute::execute(Box::pin(Runner::new(ppu.clone(), line))).unwrap();
+        ppu.borrow().shades()[..16].to_vec()
+    }
+
+    #[test]
+    fn test_sprites_priority_by_x() {
+        let ppu = setup_ppu(0x00, &[[16, 12, 2, 0x00], [16, 8, 1, 0x00]]);
+
+        assert_eq!(
+            line(&ppu),
+            vec![1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_sprites_priority_by_index() {
+        let ppu = setup_ppu(0x00, &[[16, 8, 2, 0x00], [16, 8, 1, 0x00]]);
+
+        assert_eq!(
+            line(&ppu),
+            vec![2, 2, 2, 2, 2, 2, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_sprites_behind_background() {
+        let ppu = setup_ppu(0xF0, &[[16, 8, 2, 0x80], [16, 3, 1, 0x00]]);
+
+        assert_eq!(
+            line(&ppu),
+            vec![1, 1, 1, 3, 2, 2, 2, 2, 3, 3, 3, 3, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_sprites_palettes() {
+        let ppu = setup_ppu(0x00, &[[16, 8, 1, 0x10], [16, 16, 2, 0x00]]);
+        ppu.borrow_mut().registers.set(0xFF48, 0xFC);
+        ppu.borrow_mut().registers.set(0xFF49, 0x1B);
+
+        assert_eq!(
+            line(&ppu),
+            vec![2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 3]
+        );
+    }
+
+    #[test]
+    fn test_sprites_disabled() {
+        let ppu = setup_ppu(0x00, &[[16, 8, 1, 0x00]]);
+        ppu.borrow_mut().registers.set(0xFF40, 0x91);
+
+        assert_eq!(line(&ppu), vec![0; 16]);
+    }
+
+    #[test]
+    fn test_window_layer() {
+        let ppu = setup_ppu(0x00, &[]);
+        {
+            let mut p = ppu.borrow_mut();
+            let p = &mut *p;
+            p.registers.set(0xFF40, 0xF1);
+            p.registers.set(0xFF4B, 11);
+            p.set_vram(0x9C00, 0x01).unwrap();
+            p.window.new_line(&p.registers);
+        }
+
+        assert_eq!(
+            line(&ppu),
+            vec![0, 0, 0, 0, 3, 3, 3, 3, 3, 3, 3, 3, 0, 0, 0, 0]
+        );
+        assert_eq!(ppu.borrow().window.line(), 1);
+    }
+
+    #[test]
+    fn test_fine_scroll() {
+        let ppu = setup_ppu(0x00, &[]);
+        ppu.borrow_mut().set_vram(0x9800, 0x01).unwrap();
+        ppu.borrow_mut().registers.set(0xFF43, 3);
+
+        assert_eq!(
+            line(&ppu),
+            vec![3, 3, 3, 3, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_coarse_scroll_wraps() {
+        let ppu = setup_ppu(0x00, &[]);
+        ppu.borrow_mut().set_vram(0x981F, 0x01).unwrap();
+        ppu.borrow_mut().set_vram(0x9801, 0x01).unwrap();
+        ppu.borrow_mut().registers.set(0xFF43, 0xF8);
+
+        assert_eq!(
+            line(&ppu),
+            vec![3, 3, 3, 3, 3, 3, 3, 3, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
     }
 }
@@ -3,7 +3,7 @@ use super::pixels::Row;
 use crate::futures::Fetch;
 use crate::interface::Push;
 
-use crate::registers::coordinates::XRange;
+use crate::registers::coordinates::{XRange, MAP_ROW_LEN};
 use crate::Ppu;
 use shared::Error;
 
@@ -12,24 +12,53 @@ use shared::Error;
 /// Index: Index of the tile to read
 pub struct Fetcher {
     ppu: Ppu,
-    map_row: u16,
-    x_range: XRange,
+    layer: Layer,
+}
+
+/// The background follows SCX and SCY, read again for each tile so the
+/// writes during the line show up. The window is fixed for the line
+enum Layer {
+    Background,
+    Window { map_row: u16, tile_line: usize },
 }
 
 impl Fetcher {
     pub fn new(ppu: Ppu) -> Self {
-        let mut p = ppu.borrow_mut();
-        // New line, so x is 0;
-        let map_row = p.registers.tile_map_row_address();
-        let x_range = p.registers.coordinates.x_range();
+        Self {
+            ppu,
+            layer: Layer::Background,
+        }
+    }
 
-        p.fifo.clear();
+    /// Fetch the window from its first tile, on its own line counter
+    pub fn window(ppu: Ppu) -> Self {
+        let p = ppu.borrow();
+        let line = p.window.line() as u16;
+        let map_row = p.registers.control.window_area + (line / 8) * MAP_ROW_LEN;
+        let tile_line = (line % 8) as usize;
 
         drop(p);
         Self {
             ppu,
-            map_row,
-            x_range,
+            layer: Layer::Window { map_row, tile_line },
+        }
+    }
+
+    /// Address of the `x`th tile id fetched on the line
+    fn map_address(&self, x: u8) -> u16 {
+        match self.layer {
+            Layer::Background => {
+                let registers = &self.ppu.borrow().registers;
+                registers.tile_map_row_address() + registers.coordinates.map_column(x)
+            }
+            Layer::Window { map_row, .. } => map_row + x as u16,
+        }
+    }
+
+    fn tile_line(&self) -> usize {
+        match self.layer {
+            Layer::Background => self.ppu.borrow().registers.coordinates.tile_line(),
+            Layer::Window { tile_line, .. } => tile_line,
         }
     }
 
@@ -40,12 +69,12 @@ impl Fetcher {
         // Many checks have to opperate here as the line Fetcher is complex
         // (Background, Window, Sprite)
         // Carefull implemenation
-        for x in self.x_range {
+        for x in XRange::new(0) {
             // First get the adress of the Tile id
             // This may be refactored to handle background or window id
             //println!("[FETCHER] Fetching tile id");
 
-            let map_address = self.map_row + x as u16;
+            let map_address = self.map_address(x);
             //println!("[FETCHER] Map address: {:#X}", map_address);
             let (tile_id, ticks) = Fetch::new(&self.ppu, map_address).await?;
 
@@ -54,7 +83,7 @@ impl Fetcher {
             //println!("[FETCHER] Processing tile address");
             // Then we get the address of a row of pixels in that tile
 
-            let row = Row::try_new(&self.ppu, tile_id).await?;
+            let row = Row::try_new(&self.ppu, tile_id, self.tile_line()).await?;
             // Finaly we convert that Row into a vector of pixels, and push
             // thoes in the ppu queue
             let ticks = self.ppu.push(row.into()).await;
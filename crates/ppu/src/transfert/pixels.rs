@@ -2,6 +2,8 @@ use std::convert::From;
 
 use shared::Error;
 
+use crate::oam::Sprite;
+use crate::registers::Field;
 use crate::{futures::Fetch, Ppu};
 
 const TILE_SIZE: u16 = 16;
@@ -14,7 +16,7 @@ pub struct Row {
     byte1: u8,
 }
 
-type Pixels = [u8; 8];
+pub type Pixels = [u8; 8];
 
 impl Bits {
     pub fn new(data: u8) -> Self {
@@ -45,22 +47,19 @@ impl From<Row> for Pixels {
 
 impl Row {
     /// Calculate the address of a row of pixels in a tile from Tile ID
+    /// In the 8800 area the ids are signed, tile 0 is at 9000
     fn row_address(tile_line: usize, data_area: u16, id: u8) -> u16 {
-        if data_area == 0x8000 {
-            let offset = data_area + (id as u16 * TILE_SIZE);
-            offset + (tile_line * 2) as u16
-        } else {
-            let relative = id as i16 * TILE_SIZE as i16;
-            let address = data_area as i16 + relative;
-            address as u16
-        }
+        let tile = match data_area {
+            0x8000 => id,
+            _ => id ^ 0x80,
+        };
+        data_area + (tile as u16 * TILE_SIZE) + (tile_line * 2) as u16
     }
 
     /// Get the data of a row in a tile from tile id
-    pub async fn try_new(ppu: &'_ Ppu, id: u8) -> Result<Self, Error> {
+    pub async fn try_new(ppu: &'_ Ppu, id: u8, tile_line: usize) -> Result<Self, Error> {
         let p = ppu.borrow();
         let data_area = p.registers.control.data_area;
-        let tile_line = p.registers.coordinates.tile_line();
         let address = Self::row_address(tile_line, data_area, id);
 
         drop(p);
@@ -71,6 +70,19 @@ impl Row {
 
         Ok(Self { byte0, byte1 })
     }
+
+    /// Get the row of a sprite drawn on the current line
+    pub async fn sprite(ppu: &'_ Ppu, sprite: Sprite) -> Result<Self, Error> {
+        let address = {
+            let p = ppu.borrow();
+            let ly = p.registers.coordinates.get(Field::Ly);
+            sprite.row_address(ly, p.registers.control.sprite_size)
+        };
+        let (byte0, _) = Fetch::new(ppu, address).await?;
+        let (byte1, _) = Fetch::new(ppu, address + 1).await?;
+
+        Ok(Self { byte0, byte1 })
+    }
 }
 
 #[cfg(test)]
@@ -88,7 +100,7 @@ mod test_tiles {
     /// [1, 2, 2, 3, 1, 2, 2, 3]);
     /// [1, 2, 2, 3, 1, 2, 2, 3]);
     /// [0, 3, 3, 0, 0, 3, 3, 0]);
-    fn setup_ppu(ly: u8) -> Ppu {
+    fn setup_ppu() -> Ppu {
         let interrupts = Interrupts::default();
         let ppu = Ppu::new(interrupts, true);
         let tile = vec![
@@ -100,16 +112,15 @@ mod test_tiles {
         for (index, data) in tile.iter().enumerate() {
             p.set_vram((0x8000 + index) as u16, *data).unwrap();
         }
-        p.registers.coordinates.set(crate::Field::Ly, ly);
         drop(p);
         ppu
     }
 
     #[test]
     fn test_get_tile_row_zero_from_memory() {
-        let ppu = setup_ppu(0);
+        let ppu = setup_ppu();
         let expected = [0, 0, 0, 0, 0, 3, 0, 0];
-        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0))).unwrap();
+        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0, 0))).unwrap();
         let result: Pixels = row.into();
 
         assert_eq!(result, expected);
@@ -117,9 +128,9 @@ mod test_tiles {
 
     #[test]
     fn test_get_tile_row_one_from_memory() {
-        let ppu = setup_ppu(1);
+        let ppu = setup_ppu();
         let expected = [0, 0, 0, 0, 0, 3, 0, 0];
-        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0))).unwrap();
+        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0, 1))).unwrap();
         let result: Pixels = row.into();
 
         assert_eq!(result, expected);
@@ -127,9 +138,9 @@ mod test_tiles {
 
     #[test]
     fn test_get_tile_row_two_from_memory() {
-        let ppu = setup_ppu(2);
+        let ppu = setup_ppu();
         let expected = [0, 0, 0, 0, 3, 0, 3, 0];
-        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0))).unwrap();
+        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0, 2))).unwrap();
         let result: Pixels = row.into();
 
         assert_eq!(result, expected);
@@ -137,9 +148,9 @@ mod test_tiles {
 
     #[test]
     fn test_get_tile_row_three_from_memory() {
-        let ppu = setup_ppu(3);
+        let ppu = setup_ppu();
         let expected = [0, 0, 0, 3, 0, 0, 3, 0];
-        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0))).unwrap();
+        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0, 3))).unwrap();
         let result: Pixels = row.into();
 
         assert_eq!(result, expected);
@@ -147,9 +158,9 @@ mod test_tiles {
 
     #[test]
     fn test_get_tile_row_four_from_memory() {
-        let ppu = setup_ppu(4);
+        let ppu = setup_ppu();
         let expected = [0, 1, 1, 0, 0, 1, 1, 0];
-        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0))).unwrap();
+        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0, 4))).unwrap();
         let result: Pixels = row.into();
 
         assert_eq!(result, expected);
@@ -157,9 +168,9 @@ mod test_tiles {
 
     #[test]
     fn test_get_tile_row_five_from_memory() {
-        let ppu = setup_ppu(5);
+        let ppu = setup_ppu();
         let expected = [1, 2, 2, 3, 1, 2, 2, 3];
-        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0))).unwrap();
+        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0, 5))).unwrap();
         let result: Pixels = row.into();
 
         assert_eq!(result, expected);
@@ -167,9 +178,9 @@ mod test_tiles {
 
     #[test]
     fn test_get_tile_row_six_from_memory() {
-        let ppu = setup_ppu(6);
+        let ppu = setup_ppu();
         let expected = [1, 2, 2, 3, 1, 2, 2, 3];
-        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0))).unwrap();
+        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0, 6))).unwrap();
         let result: Pixels = row.into();
 
         assert_eq!(result, expected);
@@ -177,11 +188,19 @@ mod test_tiles {
 
     #[test]
     fn test_get_tile_row_seven_from_memory() {
-        let ppu = setup_ppu(7);
+        let ppu = setup_ppu();
         let expected = [0, 3, 3, 0, 0, 3, 3, 0];
-        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0))).unwrap();
+        let row = execute::execute(Box::pin(Row::try_new(&ppu, 0, 7))).unwrap();
         let result: Pixels = row.into();
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_row_address_signed_area() {
+        assert_eq!(Row::row_address(3, 0x8800, 0x00), 0x9006);
+        assert_eq!(Row::row_address(0, 0x8800, 0x7F), 0x97F0);
+        assert_eq!(Row::row_address(7, 0x8800, 0x80), 0x880E);
+        assert_eq!(Row::row_address(7, 0x8000, 0x80), 0x880E);
+    }
 }
@@ -0,0 +1,27 @@
+use super::pixels::{Pixels, Row};
+
+use crate::fifo::Object;
+use crate::oam::Sprite;
+use crate::Ppu;
+use shared::Error;
+
+/// Fetch the row of a sprite and mix it in the sprite fifo.
+/// The background fetcher and the fifo wait in the meantime
+pub async fn fetch(ppu: &'_ Ppu, sprite: Sprite) -> Result<(), Error> {
+    let mut pixels: Pixels = Row::sprite(ppu, sprite).await?.into();
+    if sprite.x_flip() {
+        pixels.reverse();
+    }
+    // Sprites with x below 8 are partially left of the screen
+    let hidden = 8usize.saturating_sub(sprite.x as usize);
+    let objects: Vec<Object> = pixels[hidden..]
+        .iter()
+        .map(|color| Object {
+            color: *color,
+            palette: sprite.palette(),
+            behind: sprite.behind_background(),
+        })
+        .collect();
+    ppu.borrow_mut().sprite_fifo.merge(&objects);
+    Ok(())
+}
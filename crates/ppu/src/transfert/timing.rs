@@ -0,0 +1,92 @@
+/// Shortest pixel transfer, without fine scroll, window or sprites
+pub const TRANSFERT: u16 = 172;
+/// The fetcher restarts on the window map
+const WINDOW_PENALTY: u16 = 6;
+/// Fetching the row of a sprite, before waiting for the background fetcher
+const SPRITE_PENALTY: u16 = 6;
+
+/// Length of the pixel transfer of a line, from the SCX fine scroll, the
+/// window and the sprites fetched.
+///
+/// A sprite also waits for the background fetcher to finish the tile under
+/// its leftmost pixel: the pixels of that tile right of the sprite, minus 2.
+/// It only happens once per tile, the next sprites on it find the fetcher
+/// ready. A sprite at X 0 always waits for a whole tile.
+#[derive(Debug)]
+pub struct Timing {
+    cycles: u16,
+    /// Shift of the background layer drawn, SCX then the window position
+    offset: u8,
+    /// Tiles under a sprite already waited for
+    tiles: Vec<u8>,
+}
+
+impl Timing {
+    pub fn new(scx: u8) -> Self {
+        Self {
+            cycles: TRANSFERT + (scx % 8) as u16,
+            offset: scx,
+            tiles: Vec::new(),
+        }
+    }
+
+    pub fn window(&mut self, wx: u8) {
+        self.cycles += WINDOW_PENALTY;
+        self.offset = 7u8.wrapping_sub(wx);
+        self.tiles.clear();
+    }
+
+    /// `x` is the sprite position on the line plus 8, as in OAM
+    pub fn sprite(&mut self, x: u8) {
+        let wait = if x == 0 {
+            5
+        } else {
+            let position = x.wrapping_add(self.offset);
+            let tile = position / 8;
+            if self.tiles.contains(&tile) {
+                0
+            } else {
+                self.tiles.push(tile);
+                (7 - position % 8).saturating_sub(2)
+            }
+        };
+        self.cycles += SPRITE_PENALTY + wait as u16;
+    }
+
+    pub fn cycles(&self) -> u16 {
+        self.cycles
+    }
+}
+
+#[cfg(test)]
+mod test_timing {
+    use super::{Timing, TRANSFERT};
+
+    #[test]
+    fn test_timing_fine_scroll() {
+        assert_eq!(Timing::new(0).cycles(), TRANSFERT);
+        assert_eq!(Timing::new(0x0B).cycles(), TRANSFERT + 3);
+    }
+
+    #[test]
+    fn test_timing_sprites() {
+        let mut timing = Timing::new(0);
+        timing.sprite(8);
+        assert_eq!(timing.cycles(), TRANSFERT + 11);
+        timing.sprite(10);
+        assert_eq!(timing.cycles(), TRANSFERT + 17);
+        timing.sprite(22);
+        assert_eq!(timing.cycles(), TRANSFERT + 23);
+        timing.sprite(0);
+        assert_eq!(timing.cycles(), TRANSFERT + 34);
+    }
+
+    #[test]
+    fn test_timing_window() {
+        let mut timing = Timing::new(0);
+        timing.sprite(8);
+        timing.window(7);
+        timing.sprite(8);
+        assert_eq!(timing.cycles(), TRANSFERT + 6 + 22);
+    }
+}
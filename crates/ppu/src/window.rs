@@ -0,0 +1,143 @@
+use crate::registers::{Field, Registers};
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
+
+/// Window position is WX - 7, past 166 it is never drawn
+const WX_OFFSET: u8 = 7;
+const WX_MAX: u8 = 166;
+
+/// The window keeps its own line counter: it only moves on the lines where
+/// the window was drawn, so disabling the window mid-frame and enabling it
+/// again resumes where it stopped instead of following LY
+#[derive(Debug, Default)]
+pub struct Window {
+    /// LY matched WY on a line of this frame, with the window enabled
+    triggered: bool,
+    /// Line of the window drawn next
+    line: u8,
+    /// The window was drawn on the current line
+    drawn: bool,
+}
+
+impl Window {
+    /// At the start of each line, before the OAM scan
+    pub fn new_line(&mut self, registers: &Registers) {
+        let ly = registers.coordinates.get(Field::Ly);
+        if registers.control.window_enabled && ly == registers.coordinates.get(Field::Ywindow) {
+            self.triggered = true;
+        }
+        self.drawn = false;
+    }
+
+    /// Start the window if it begins on the pixel `x` of the line, and get
+    /// the number of its pixels left of the screen (WX below 7)
+    pub fn start(&mut self, registers: &Registers, x: usize) -> Option<u8> {
+        let wx = registers.coordinates.get(Field::Xwindow);
+        let visible = self.triggered && registers.control.window_enabled && wx <= WX_MAX;
+        if self.drawn || !visible || x != wx.saturating_sub(WX_OFFSET) as usize {
+            return None;
+        }
+        self.drawn = true;
+        Some(WX_OFFSET.saturating_sub(wx))
+    }
+
+    /// Line of the window to draw
+    pub fn line(&self) -> u8 {
+        self.line
+    }
+
+    pub fn end_line(&mut self) {
+        if self.drawn {
+            self.line += 1;
+        }
+    }
+
+    pub fn new_frame(&mut self) {
+        self.triggered = false;
+        self.line = 0;
+    }
+}
+
+impl Snapshot for Window {
+    fn snapshot(&self, writer: &mut Writer) {
+        writer.bool(self.triggered);
+        writer.u8(self.line);
+        writer.bool(self.drawn);
+    }
+
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        self.triggered = reader.bool()?;
+        self.line = reader.u8()?;
+        self.drawn = reader.bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_window {
+    use super::Window;
+    use crate::registers::{Field, Registers};
+
+    fn setup_registers(wy: u8, wx: u8) -> Registers {
+        let mut registers = Registers::new();
+        registers.set(0xFF40, 0xB1);
+        registers.coordinates.set(Field::Ywindow, wy);
+        registers.coordinates.set(Field::Xwindow, wx);
+        registers
+    }
+
+    fn draw_line(window: &mut Window, registers: &mut Registers, ly: u8) -> Option<u8> {
+        registers.coordinates.set(Field::Ly, ly);
+        window.new_line(registers);
+        let start = (0..160).find_map(|x| window.start(registers, x));
+        window.end_line();
+        start
+    }
+
+    #[test]
+    fn test_window_starts_at_wx_minus_7() {
+        let registers = setup_registers(0, 20);
+        let mut window = Window::default();
+        window.new_line(&registers);
+
+        assert_eq!(window.start(&registers, 12), None);
+        assert_eq!(window.start(&registers, 13), Some(0));
+        assert_eq!(window.start(&registers, 14), None);
+    }
+
+    #[test]
+    fn test_window_hidden_pixels() {
+        let registers = setup_registers(0, 3);
+        let mut window = Window::default();
+        window.new_line(&registers);
+
+        assert_eq!(window.start(&registers, 0), Some(4));
+    }
+
+    #[test]
+    fn test_window_waits_for_wy() {
+        let mut registers = setup_registers(2, 7);
+        let mut window = Window::default();
+
+        assert_eq!(draw_line(&mut window, &mut registers, 1), None);
+        assert_eq!(draw_line(&mut window, &mut registers, 2), Some(0));
+        assert_eq!(draw_line(&mut window, &mut registers, 3), Some(0));
+        assert_eq!(window.line(), 2);
+    }
+
+    #[test]
+    fn test_window_line_resumes_after_disable() {
+        let mut registers = setup_registers(0, 7);
+        let mut window = Window::default();
+
+        draw_line(&mut window, &mut registers, 0);
+        registers.set(0xFF40, 0x91);
+        assert_eq!(draw_line(&mut window, &mut registers, 1), None);
+        registers.set(0xFF40, 0xB1);
+        draw_line(&mut window, &mut registers, 2);
+        assert_eq!(window.line(), 2);
+
+        window.new_frame();
+        assert_eq!(draw_line(&mut window, &mut registers, 3), None);
+    }
+}
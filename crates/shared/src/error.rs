@@ -12,6 +12,7 @@ pub enum Error {
     RamLock(usize),
     Unimplemented(u8),
     FailedRamLoad,
+    InvalidState,
 }
 
 impl fmt::Display for Error {
@@ -37,6 +38,7 @@ impl fmt::Display for Error {
             Error::DisabledInterrupts => write!(f, "Disabled interrupts"),
             Error::FifoNotReady => write!(f, "Fifo Not Ready"),
             Error::FailedRamLoad => write!(f, "Could not open save file"),
+            Error::InvalidState => write!(f, "Invalid save state"),
         }
     }
 }
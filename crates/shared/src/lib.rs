@@ -3,6 +3,7 @@ pub mod execute;
 pub mod interrupts;
 pub mod redraw;
 pub mod run;
+pub mod snapshot;
 pub mod waker;
 
 pub use error::Error;
@@ -11,3 +12,4 @@ pub use interrupts::interface::Interrupts;
 pub use interrupts::Interrupt;
 pub use redraw::Redraw;
 pub use run::{Finished, Output, Run};
+pub use snapshot::Snapshot;
@@ -0,0 +1,164 @@
+use crate::Error;
+
+/// Components of the machine that are written to a save state. The layout of
+/// a component is the order of its fields, bump the state version when it changes.
+pub trait Snapshot {
+    fn snapshot(&self, writer: &mut Writer);
+    fn restore(&mut self, reader: &mut Reader) -> Result<(), Error>;
+}
+
+/// Little endian binary writer of the save states
+#[derive(Debug, Default)]
+pub struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    pub fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    /// Length prefixed bytes
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.u32(value.len() as u32);
+        self.data.extend(value);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Reader of the data written by a `Writer`, running out of data is an `InvalidState`
+#[derive(Debug)]
+pub struct Reader<'data> {
+    data: &'data [u8],
+}
+
+impl<'data> Reader<'data> {
+    pub fn new(data: &'data [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        if self.data.len() < N {
+            return Err(Error::InvalidState);
+        }
+        let (head, tail) = self.data.split_at(N);
+        self.data = tail;
+        Ok(head.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::InvalidState),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    pub fn usize(&mut self) -> Result<usize, Error> {
+        usize::try_from(self.u64()?).map_err(|_| Error::InvalidState)
+    }
+
+    pub fn bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let len = self.u32()? as usize;
+        if self.data.len() < len {
+            return Err(Error::InvalidState);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head.to_vec())
+    }
+
+    /// Bytes of a fixed size buffer, a different length is an `InvalidState`
+    pub fn bytes_into(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        let bytes = self.bytes()?;
+        if bytes.len() != buffer.len() {
+            return Err(Error::InvalidState);
+        }
+        buffer.copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test_snapshot {
+    use super::{Reader, Writer};
+
+    #[test]
+    fn test_write_read() {
+        let mut writer = Writer::new();
+        writer.u8(0x42);
+        writer.bool(true);
+        writer.u16(0xBEEF);
+        writer.u64(u64::MAX);
+        writer.bytes(&[1, 2, 3]);
+        let data = writer.finish();
+
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.u8().unwrap(), 0x42);
+        assert!(reader.bool().unwrap());
+        assert_eq!(reader.u16().unwrap(), 0xBEEF);
+        assert_eq!(reader.u64().unwrap(), u64::MAX);
+        assert_eq!(reader.bytes().unwrap(), vec![1, 2, 3]);
+        assert!(reader.is_empty());
+        assert!(reader.u8().is_err());
+    }
+
+    #[test]
+    fn test_read_truncated() {
+        let mut writer = Writer::new();
+        writer.bytes(&[0; 16]);
+        let data = writer.finish();
+
+        let mut reader = Reader::new(&data[..10]);
+        assert!(reader.bytes().is_err());
+        let mut reader = Reader::new(&data);
+        assert!(reader.bytes_into(&mut [0; 8]).is_err());
+    }
+}
@@ -0,0 +1,13 @@
+/// Host side events raised while running, they are
+/// queued by the SOC until the frontend takes them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The rumble motor was turned on (true) or off (false)
+    Rumble(bool),
+    /// The save state requested for this slot was written
+    StateSaved(u8),
+    /// The machine went back in time, the screen must be redrawn
+    Rewound,
+    /// The CPU or the PPU failed, the machine was stopped
+    Error(String),
+}
@@ -2,6 +2,8 @@ use std::cell::RefCell;
 use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
 
+use crate::soc::Options;
+
 pub type SOC = Rc<RefCell<crate::soc::SOC>>;
 
 #[derive(Default, Debug, Clone)]
@@ -33,11 +35,19 @@ pub trait TryInit {
     fn try_init(rom: &str) -> Result<Self, std::io::Error>
     where
         Self: std::marker::Sized;
+
+    fn try_init_with(rom: &str, options: &Options) -> Result<Self, std::io::Error>
+    where
+        Self: std::marker::Sized;
 }
 
 impl TryInit for SOC {
     fn try_init(rom: &str) -> Result<Self, std::io::Error> {
-        let soc = crate::soc::SOC::try_from(rom)?;
+        Self::try_init_with(rom, &Options::default())
+    }
+
+    fn try_init_with(rom: &str, options: &Options) -> Result<Self, std::io::Error> {
+        let soc = crate::soc::SOC::new(rom, options)?;
         Ok(Rc::new(RefCell::new(soc)))
     }
 }
@@ -1,7 +1,12 @@
+pub mod event;
 pub mod interface;
 pub mod mode;
+pub mod rewind;
 pub(crate) mod runner;
 pub mod soc;
+pub(crate) mod state;
 pub mod system;
 
+pub use crate::event::Event;
 pub use crate::interface::{System, TryInit, SOC};
+pub use crate::soc::Options;
@@ -0,0 +1,218 @@
+use std::collections::VecDeque;
+
+/// Frames between two snapshots
+pub const DEFAULT_INTERVAL: u64 = 1;
+/// Bytes kept for the snapshots, the oldest ones are dropped past it
+pub const DEFAULT_BUDGET: usize = 64 * 1024 * 1024;
+
+/// Ring buffer of machine snapshots taken every `interval` frames. The newest
+/// one is kept whole, each older one is stored as the run length encoded XOR
+/// against the snapshot that follows it, so dropping the oldest is free.
+#[derive(Debug)]
+pub struct Rewind {
+    interval: u64,
+    budget: usize,
+    newest: Option<(u64, Vec<u8>)>,
+    deltas: VecDeque<Delta>,
+    size: usize,
+}
+
+#[derive(Debug)]
+struct Delta {
+    frame: u64,
+    len: usize,
+    data: Vec<u8>,
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new(DEFAULT_INTERVAL, DEFAULT_BUDGET)
+    }
+}
+
+impl Rewind {
+    pub fn new(interval: u64, budget: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            budget,
+            newest: None,
+            deltas: VecDeque::new(),
+            size: 0,
+        }
+    }
+
+    /// A snapshot should be taken once `frame` frames were completed
+    pub fn due(&self, frame: u64) -> bool {
+        let taken = matches!(self.newest, Some((newest, _)) if newest >= frame);
+        frame.is_multiple_of(self.interval) && !taken
+    }
+
+    pub fn push(&mut self, frame: u64, state: Vec<u8>) {
+        if let Some((newest, previous)) = self.newest.take() {
+            let data = encode(&xor(&previous, &state));
+            // The delta may be larger than the snapshot it replaces
+            self.size = self.size + data.len() + state.len() - previous.len();
+            self.deltas.push_back(Delta {
+                frame: newest,
+                len: previous.len(),
+                data,
+            });
+        } else {
+            self.size = state.len();
+        }
+        self.newest = Some((frame, state));
+        while self.size > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.size -= delta.data.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Drop the snapshots taken after `frame` and return the newest one left
+    pub fn rewind(&mut self, frame: u64) -> Option<(u64, Vec<u8>)> {
+        while let Some((newest, state)) = self.newest.take() {
+            if newest <= frame {
+                self.newest = Some((newest, state.clone()));
+                return Some((newest, state));
+            }
+            self.size -= state.len();
+            if let Some(delta) = self.deltas.pop_back() {
+                let mut older = xor(&state, &decode(&delta.data));
+                older.truncate(delta.len);
+                self.size = self.size + older.len() - delta.data.len();
+                self.newest = Some((delta.frame, older));
+            }
+        }
+        None
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.size = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len() + self.newest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// Bytes used by the snapshots
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// Snapshots may differ in length, the shortest is padded with zeros
+fn xor(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let len = left.len().max(right.len());
+    (0..len)
+        .map(|i| left.get(i).unwrap_or(&0) ^ right.get(i).unwrap_or(&0))
+        .collect()
+}
+
+/// Runs of zeros then literal bytes: [zeros: u32][literals: u32][literal bytes] ...
+fn encode(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut index = 0;
+    while index < data.len() {
+        let zeros = data[index..].iter().take_while(|byte| **byte == 0).count();
+        index += zeros;
+        let literals = data[index..].iter().take_while(|byte| **byte != 0).count();
+        encoded.extend((zeros as u32).to_le_bytes());
+        encoded.extend((literals as u32).to_le_bytes());
+        encoded.extend(&data[index..index + literals]);
+        index += literals;
+    }
+    encoded
+}
+
+fn decode(encoded: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut index = 0;
+    let word = |index: usize| u32::from_le_bytes(encoded[index..index + 4].try_into().unwrap());
+    while index < encoded.len() {
+        let zeros = word(index) as usize;
+        let literals = word(index + 4) as usize;
+        index += 8;
+        data.resize(data.len() + zeros, 0);
+        data.extend(&encoded[index..index + literals]);
+        index += literals;
+    }
+    data
+}
+
+#[cfg(test)]
+mod test_rewind {
+    use super::{decode, encode, Rewind};
+
+    fn state(frame: u8, len: usize) -> Vec<u8> {
+        let mut state = vec![0x42; len];
+        state[frame as usize] = frame;
+        state
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let data = vec![0, 0, 0, 1, 2, 0, 3, 0, 0];
+        assert_eq!(decode(&encode(&data)), data);
+        assert_eq!(encode(&[0; 1000]).len(), 8);
+    }
+
+    #[test]
+    fn test_rewind_to_older_snapshots() {
+        let mut rewind = Rewind::new(1, usize::MAX);
+        for frame in 1..=10u8 {
+            rewind.push(frame as u64, state(frame, 64 + frame as usize));
+        }
+
+        assert_eq!(rewind.rewind(7), Some((7, state(7, 71))));
+        assert_eq!(rewind.rewind(7), Some((7, state(7, 71))));
+        assert_eq!(rewind.rewind(2), Some((2, state(2, 66))));
+        assert_eq!(rewind.len(), 2);
+        assert_eq!(rewind.rewind(0), None);
+        assert!(rewind.is_empty());
+    }
+
+    #[test]
+    fn test_rewind_budget() {
+        let mut rewind = Rewind::new(1, 1024 + 64);
+        for frame in 1..=100u8 {
+            rewind.push(frame as u64, state(frame, 1024));
+        }
+
+        assert!(rewind.size() <= 1024 + 64);
+        assert!(rewind.len() > 1);
+        assert_eq!(rewind.rewind(99), Some((99, state(99, 1024))));
+        assert_eq!(rewind.rewind(10), None);
+    }
+
+    #[test]
+    fn test_rewind_fragmented_delta() {
+        let mut rewind = Rewind::new(1, usize::MAX);
+        let alternating = (0..64).map(|i| i as u8 % 2).collect::<Vec<_>>();
+        rewind.push(1, vec![0; 64]);
+        rewind.push(2, Vec::new());
+        rewind.push(3, alternating.clone());
+
+        // Each alternating byte costs a run header: the delta outgrows the state
+        assert_eq!(rewind.size(), 8 + 32 * 9 + 64);
+        assert_eq!(rewind.rewind(2), Some((2, Vec::new())));
+        assert_eq!(rewind.rewind(1), Some((1, vec![0; 64])));
+        assert_eq!(rewind.size(), 64);
+    }
+
+    #[test]
+    fn test_rewind_interval() {
+        let mut rewind = Rewind::new(4, usize::MAX);
+
+        assert!(!rewind.due(3));
+        assert!(rewind.due(4));
+        rewind.push(4, vec![0]);
+        assert!(!rewind.due(4));
+    }
+}
@@ -12,6 +12,9 @@ enum Processor {
 pub struct Runner {
     pub memory: Memory,
     tasks: Tasks,
+    /// Both the CPU and PPU futures completed on the last tick, the fresh
+    /// ones were not polled yet so the whole machine state is in the components.
+    settled: bool,
 }
 
 impl Runner {
@@ -22,7 +25,11 @@ impl Runner {
             memory::State::Rom => Cpu::new(memory.clone(), false),
         };
         let tasks = Tasks::new(cpu, ppu);
-        Self { memory, tasks }
+        Self {
+            memory,
+            tasks,
+            settled: true,
+        }
     }
 
     pub fn run(&mut self) -> Vec<Finished> {
@@ -32,7 +39,22 @@ impl Runner {
         self.memory.borrow_mut().clock_tick();
         let cpu_status = self.tasks.run(Processor::Cpu, &mut context);
         let ppu_status = self.tasks.run(Processor::Ppu, &mut context);
-        vec![cpu_status, ppu_status]
+        self.settled = cpu_status.is_ready() && ppu_status.is_ready();
+        let finished = |status| match status {
+            Poll::Ready(finished) => finished,
+            Poll::Pending => Finished::Nope,
+        };
+        vec![finished(cpu_status), finished(ppu_status)]
+    }
+
+    pub fn settled(&self) -> bool {
+        self.settled
+    }
+
+    /// Start over from the state of the components, after a save state was restored
+    pub fn reset(&mut self) {
+        self.tasks = Tasks::new(self.cpu(), self.ppu());
+        self.settled = true;
     }
 
     pub fn cpu(&self) -> Cpu {
@@ -63,24 +85,24 @@ impl Tasks {
         }
     }
 
-    fn run(&mut self, processor: Processor, context: &mut Context) -> Finished {
+    fn run(&mut self, processor: Processor, context: &mut Context) -> Poll<Finished> {
         match processor {
             Processor::Ppu => match self.ppu_process.as_mut().poll(context) {
                 Poll::Ready(status) => {
                     self.ppu_process = self.ppu.clone().run();
-                    Finished::finish(status)
+                    Poll::Ready(Finished::finish(status))
                 }
-                Poll::Pending => Finished::Nope,
+                Poll::Pending => Poll::Pending,
             },
             Processor::Cpu => match self.cpu_process.as_mut().poll(context) {
                 Poll::Ready(status) => {
                     self.cpu_process = self.cpu.clone().run();
                     self.cpu.borrow_mut().print_debug();
-                    Finished::finish(status)
+                    Poll::Ready(Finished::finish(status))
                 }
                 Poll::Pending => {
                     self.cpu.borrow_mut().print_debug();
-                    Finished::Nope
+                    Poll::Pending
                 }
             },
         }
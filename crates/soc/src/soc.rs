@@ -1,40 +1,93 @@
+use crate::rewind::Rewind;
 use crate::runner::Runner;
-use crate::System;
-use shared::Redraw;
+use crate::state;
+use crate::{Event, System};
+use shared::{Finished, Redraw};
 use std::fs;
+use std::path::PathBuf;
 
 use memory;
 use memory::header::Header;
 
-const HEADER_START: usize = 0x100;
-const HEADER_END: usize = 0x150;
+/// How a ROM is loaded, see `SOC::new`
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Boot ROM run before the cartridge, without it the machine starts at 0x0100
+    /// with the registers the boot ROM leaves behind
+    pub bios: Option<PathBuf>,
+    /// Where the battery saves and save states are kept
+    pub saves: memory::SaveManager,
+    /// Addresses the debugger breaks on, in order
+    pub breakpoints: Vec<u16>,
+}
 
 /// The SOC is the GBMU async executor
 pub struct SOC {
     status: System,
     processor: Runner,
+    rumble: bool,
+    events: Vec<Event>,
+    /// Global checksum of the ROM, save states of other games are refused
+    checksum: u16,
+    slots: memory::Slots,
+    /// Slot of the save state to write once the runner settled
+    pending_state: Option<u8>,
+    rewind: Rewind,
+    /// Frames completed since the ROM was loaded, the rewind snapshots are tagged with it
+    frames: u64,
+    /// Clock ticks since the ROM was loaded
+    cycles: u64,
+    /// A rewind snapshot is due once the runner settles
+    pending_rewind: bool,
+    breakpoints: Vec<u16>,
 }
 
 impl TryFrom<&str> for SOC {
     type Error = std::io::Error;
 
     fn try_from(path: &str) -> Result<Self, Self::Error> {
+        Self::new(path, &Options::default())
+    }
+}
+
+impl SOC {
+    /// Load the ROM at `path`, its battery save is kept where `options.saves` tells
+    pub fn new(path: &str, options: &Options) -> std::io::Result<Self> {
         let rom = fs::read(path)?;
-        let raw_header = rom[HEADER_START..HEADER_END].to_vec();
+        let bios = options.bios.as_ref().map(fs::read).transpose()?;
+        let start = Header::locate(&rom);
+        let raw_header = rom[start..start + memory::header::HEADER_LEN].to_vec();
 
         let header = Header::try_from(raw_header).expect("Invalid data in raw_header");
         println!("Header: {:#?}", header);
 
-        let state = memory::state::State::Rom;
-        let memory: memory::Memory = memory::memory::Memory::new(header, rom, state);
+        let state = match bios {
+            Some(_) => memory::state::State::Bios,
+            None => memory::state::State::Rom,
+        };
+        let save = options.saves.open(std::path::Path::new(path), &header);
+        let slots = options.saves.slots(std::path::Path::new(path), &header);
+        let checksum = header.global_checksum;
+        let memory: memory::Memory = memory::memory::Memory::new(header, rom, bios, save);
         let processor = Runner::new(memory, state);
         let status = System::new(processor.cpu());
 
-        Ok(SOC { processor, status })
+        Ok(SOC {
+            processor,
+            status,
+            rumble: false,
+            events: Vec::new(),
+            checksum,
+            slots,
+            pending_state: None,
+            rewind: Rewind::default(),
+            frames: 0,
+            cycles: 0,
+            pending_rewind: false,
+            breakpoints: options.breakpoints.clone(),
+        })
     }
-}
 
-impl SOC {
     pub fn get_ppu(&self) -> ppu::Ppu {
         self.processor.ppu()
     }
@@ -51,18 +104,251 @@ impl SOC {
         self.status.clone()
     }
 
+    /// Frames completed since the ROM was loaded
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Clock ticks since the ROM was loaded, 4_194_304 per second
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Breakpoints given when the ROM was loaded
+    pub fn breakpoints(&self) -> &[u16] {
+        &self.breakpoints
+    }
+
+    pub fn get_rumble(&self) -> bool {
+        self.rumble
+    }
+
+    /// Tilt of the console in g, from -1.0 to 1.0, x to the right and y to the bottom
+    pub fn set_tilt(&self, x: f32, y: f32) {
+        self.processor.memory.borrow().set_tilt(x, y)
+    }
+
+    pub fn set_camera_image(&self, image: &str) -> std::io::Result<()> {
+        let memory = self.processor.memory.borrow();
+        memory.set_camera_image(std::path::Path::new(image))
+    }
+
+    pub fn export_photos(&self, directory: &str) -> std::io::Result<Vec<std::path::PathBuf>> {
+        let memory = self.processor.memory.borrow();
+        memory.export_photos(std::path::Path::new(directory))
+    }
+
+    /// Plug the other end of the cartridge infrared port, see `memory::InfraredLink`
+    /// to connect two SOC together
+    pub fn set_infrared(&self, peer: Box<dyn memory::Infrared>) {
+        self.processor.memory.borrow().set_infrared(peer)
+    }
+
+    /// See `cpu::cpu::Cpu::set_decode_hook`
+    pub fn set_decode_hook<F>(&self, hook: F)
+    where
+        F: FnMut(u8, &cpu::Registers) + 'static,
+    {
+        self.processor.cpu().borrow_mut().set_decode_hook(hook)
+    }
+
+    /// Keep the serial output for `take_serial` instead of printing it
+    pub fn capture_serial(&self) {
+        self.processor.memory.borrow_mut().capture_serial()
+    }
+
+    /// Drain the serial output captured since the last call
+    pub fn take_serial(&self) -> Vec<u8> {
+        self.processor.memory.borrow_mut().take_serial()
+    }
+
+    /// Drain the events raised since the last call
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// The whole machine state, only available between two ticks where both
+    /// the CPU and the PPU finished their work.
+    pub fn snapshot(&self) -> Option<Vec<u8>> {
+        self.processor
+            .settled()
+            .then(|| state::save(&self.processor, self.checksum))
+    }
+
+    /// The rewind buffer is emptied, its snapshots belong to another timeline
+    pub fn restore(&mut self, data: &[u8]) -> Result<(), shared::Error> {
+        state::load(&mut self.processor, self.checksum, data)?;
+        self.rewind.clear();
+        self.pending_rewind = false;
+        Ok(())
+    }
+
+    /// Write a save state in `slot` as soon as the machine settles,
+    /// `Event::StateSaved` is raised once it is written.
+    pub fn save_state(&mut self, slot: u8) {
+        self.pending_state = Some(slot);
+        self.write_pending_state();
+    }
+
+    pub fn load_state(&mut self, slot: u8) -> std::io::Result<()> {
+        let data = fs::read(self.slots.path(slot))?;
+        self.restore(&data)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    fn write_pending_state(&mut self) {
+        if let (Some(slot), Some(data)) = (self.pending_state, self.snapshot()) {
+            self.pending_state = None;
+            match fs::write(self.slots.path(slot), data) {
+                Ok(()) => self.events.push(Event::StateSaved(slot)),
+                Err(e) => println!("Could not write the save state {}: {}", slot, e),
+            }
+        }
+    }
+
+    /// Take a rewind snapshot every `interval` frames, keeping at most `budget` bytes of them
+    pub fn set_rewind(&mut self, interval: u64, budget: usize) {
+        self.rewind = Rewind::new(interval, budget);
+        self.pending_rewind = false;
+    }
+
+    /// Go back to the previous frame: the closest older snapshot is restored
+    /// then replayed up to the end of that frame, so the screen shows it.
+    /// Returns false when the rewind buffer has nothing older.
+    pub fn step_back(&mut self) -> bool {
+        let target = match self.frames.checked_sub(1) {
+            Some(target) if target > 0 => target,
+            _ => return false,
+        };
+        let (frame, data) = match self.rewind.rewind(target - 1) {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        if let Err(e) = state::load(&mut self.processor, self.checksum, &data) {
+            println!("Could not rewind: {}", e);
+            self.rewind.clear();
+            return false;
+        }
+        self.frames = frame;
+        self.pending_rewind = false;
+        while self.frames < target {
+            self.tick();
+        }
+        self.events.push(Event::Rewound);
+        true
+    }
+
+    /// Run the machine for one clock tick and do the host side work
+    fn tick(&mut self) -> Vec<Finished> {
+        let finished = self.processor.run();
+        self.cycles += 1;
+        for status in &finished {
+            match status {
+                Finished::Frame => {
+                    self.frames += 1;
+                    self.pending_rewind |= self.rewind.due(self.frames);
+                }
+                Finished::Error(error) => self.events.push(Event::Error(error.to_string())),
+                _ => {}
+            }
+        }
+        if self.pending_rewind {
+            if let Some(data) = self.snapshot() {
+                self.pending_rewind = false;
+                self.rewind.push(self.frames, data);
+            }
+        }
+        if self.pending_state.is_some() {
+            self.write_pending_state();
+        }
+        let rumble = self.processor.memory.borrow().get_rumble();
+        if rumble != self.rumble {
+            self.rumble = rumble;
+            self.events.push(Event::Rumble(rumble));
+        }
+        finished
+    }
+
     pub fn run(&mut self) -> Redraw {
-        let mut status = self.status.borrow_mut();
+        let system = self.status.clone();
+        let mut status = system.borrow_mut();
         status.redraw.clear();
         if status.is_idle() {
             return Redraw::Nope;
         }
         while status.processing() {
             status.step();
-            let finished = self.processor.run();
-            status.check_redraw(finished)
+            let finished = self.tick();
+            status.check_redraw(finished);
         }
         //println!("[SOC] Finished Run. Redraw: {:?}", status.redraw);
         status.redraw
     }
 }
+
+#[cfg(test)]
+mod test_soc {
+    use super::SOC;
+
+    /// A ROM counting in A and writing it all over WRAM
+    fn rom(name: &str) -> String {
+        let rom = headless::build_rom(name, &[0x21, 0x00, 0xC0, 0x3C, 0x77, 0x23, 0x18, 0xFB]);
+        rom.to_str().unwrap().to_string()
+    }
+
+    fn run_until_settled(soc: &mut SOC, ticks: usize) -> Vec<u8> {
+        (0..ticks).for_each(|_| drop(soc.processor.run()));
+        loop {
+            if let Some(state) = soc.snapshot() {
+                return state;
+            }
+            soc.processor.run();
+        }
+    }
+
+    #[test]
+    fn test_restore_is_bit_identical() {
+        let mut soc = SOC::try_from(rom("gbmu_test_state.gb").as_str()).unwrap();
+        let state = run_until_settled(&mut soc, 10_000);
+        let expected = run_until_settled(&mut soc, 80_000);
+        assert_ne!(state, expected);
+
+        soc.restore(&state).unwrap();
+        assert_eq!(soc.snapshot().unwrap(), state);
+        assert_eq!(run_until_settled(&mut soc, 80_000), expected);
+    }
+
+    fn run_until_frame(soc: &mut SOC, frame: u64) -> Vec<u8> {
+        while soc.frames < frame {
+            soc.tick();
+        }
+        crate::state::save(&soc.processor, soc.checksum)
+    }
+
+    #[test]
+    fn test_step_back_replays_previous_frame() {
+        let mut soc = SOC::try_from(rom("gbmu_test_rewind.gb").as_str()).unwrap();
+        let previous = run_until_frame(&mut soc, 4);
+        let current = run_until_frame(&mut soc, 5);
+        assert_ne!(previous, current);
+
+        assert!(soc.step_back());
+        assert_eq!(soc.frames, 4);
+        assert_eq!(crate::state::save(&soc.processor, soc.checksum), previous);
+        assert_eq!(run_until_frame(&mut soc, 5), current);
+    }
+
+    #[test]
+    fn test_refuse_invalid_state() {
+        let mut soc = SOC::try_from(rom("gbmu_test_invalid_state.gb").as_str()).unwrap();
+        let state = run_until_settled(&mut soc, 1_000);
+        run_until_settled(&mut soc, 1_000);
+        let current = run_until_settled(&mut soc, 0);
+
+        assert!(soc.restore(&state[..state.len() - 1]).is_err());
+        let mut other_game = state.clone();
+        other_game[6] ^= 0xFF;
+        assert!(soc.restore(&other_game).is_err());
+        assert_eq!(soc.snapshot().unwrap(), current);
+    }
+}
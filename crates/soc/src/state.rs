@@ -0,0 +1,53 @@
+use crate::runner::Runner;
+use shared::snapshot::{Reader, Snapshot, Writer};
+use shared::Error;
+
+const MAGIC: &[u8; 4] = b"GBMU";
+/// Bumped each time the layout of a component changes, older states are refused
+pub const VERSION: u16 = 6;
+
+/// A save state is the magic, the version, the global checksum of the ROM it
+/// belongs to, then the CPU and the memory (PPU, IO, interrupts and cartridge).
+/// It can only be taken once the runner settled, see `Runner::settled`.
+pub(crate) fn save(runner: &Runner, checksum: u16) -> Vec<u8> {
+    let mut writer = Writer::new();
+    MAGIC.iter().for_each(|byte| writer.u8(*byte));
+    writer.u16(VERSION);
+    writer.u16(checksum);
+    runner.cpu().borrow().snapshot(&mut writer);
+    runner.memory.borrow().snapshot(&mut writer);
+    writer.finish()
+}
+
+/// The machine is left untouched when the state is refused
+pub(crate) fn load(runner: &mut Runner, checksum: u16, data: &[u8]) -> Result<(), Error> {
+    let backup = save(runner, checksum);
+    match restore(runner, checksum, data) {
+        Ok(()) => {
+            runner.reset();
+            Ok(())
+        }
+        Err(error) => {
+            restore(runner, checksum, &backup).expect("Could not restore the machine");
+            Err(error)
+        }
+    }
+}
+
+fn restore(runner: &mut Runner, checksum: u16, data: &[u8]) -> Result<(), Error> {
+    let mut reader = Reader::new(data);
+    for byte in MAGIC {
+        if reader.u8()? != *byte {
+            return Err(Error::InvalidState);
+        }
+    }
+    if reader.u16()? != VERSION || reader.u16()? != checksum {
+        return Err(Error::InvalidState);
+    }
+    runner.cpu().borrow_mut().restore(&mut reader)?;
+    runner.memory.borrow_mut().restore(&mut reader)?;
+    if !reader.is_empty() {
+        return Err(Error::InvalidState);
+    }
+    Ok(())
+}
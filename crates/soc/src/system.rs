@@ -17,10 +17,17 @@ pub struct System {
 }
 
 impl System {
+    /// The fields are spelled out, a default `Cpu` would load the BIOS from the repository
     pub fn new(cpu: cpu::Cpu) -> Self {
         Self {
             cpu,
-            ..Self::default()
+            mode: Mode::default(),
+            redraw: Redraw::default(),
+            lines: 0,
+            ticks: 0,
+            last_cpu_cycle: 0,
+            last_line_cycle: 0,
+            frames: 0,
         }
     }
     //  The order of priority of the matches really matters.
@@ -0,0 +1,169 @@
+//! The mooneye-gb acceptance suite reports its result by executing `LD B,B`,
+//! with the Fibonacci numbers 3/5/8/13/21/34 in B/C/D/E/H/L when the test
+//! passed. The suite runs `mooneye/acceptance`, see `headless::test_roms`.
+
+use cpu::registers::{Bits8, Bus};
+use headless::{build_rom, test_roms, Headless, Outcome};
+use soc::{TryInit, SOC};
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// `LD B,B`, the software breakpoint of the suite
+const LD_B_B: u8 = 0x40;
+const FIBONACCI: [u8; 6] = [3, 5, 8, 13, 21, 34];
+/// Ten emulated seconds
+const FRAMES: u64 = 10 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Report {
+    Passed,
+    Failed,
+    Timeout,
+    Error,
+}
+
+/// The suffix lists the models a test is meant for, either as groups such as
+/// `di_timing-GS` (G is the DMG and MGB) or as models with their revisions such
+/// as `boot_regs-dmgABC` or `boot_div-dmgABCmgb`. Only the DMG ABC is emulated,
+/// `boot_regs-dmg0`, `boot_regs-mgb` or `boot_hwio-S` are not run.
+fn runs_on_dmg(rom: &Path) -> bool {
+    let name = rom.file_stem().unwrap().to_string_lossy();
+    let models = match name.rsplit_once('-') {
+        Some((_, models)) => models,
+        None => return true,
+    };
+    if models.starts_with(|c: char| c.is_ascii_uppercase()) {
+        return models.contains('G');
+    }
+    match models.find("dmg") {
+        // No revision after the model means all of them
+        Some(start) => {
+            let revisions: String = models[start + 3..]
+                .chars()
+                .take_while(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+                .collect();
+            revisions.is_empty() || revisions.contains(['A', 'B', 'C'])
+        }
+        None => false,
+    }
+}
+
+fn find(directory: &Path, roms: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(directory).unwrap().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find(&path, roms);
+        } else if path.extension().is_some_and(|ext| ext == "gb") && runs_on_dmg(&path) {
+            roms.push(path);
+        }
+    }
+}
+
+fn run(rom: &Path) -> Report {
+    let soc = match SOC::try_init(rom.to_str().unwrap()) {
+        Ok(soc) => soc,
+        Err(_) => return Report::Error,
+    };
+    let result = Rc::new(Cell::new(None));
+    let hook = result.clone();
+    soc.borrow().set_decode_hook(move |opcode, registers| {
+        if opcode == LD_B_B {
+            let signature = [Bits8::B, Bits8::C, Bits8::D, Bits8::E, Bits8::H, Bits8::L]
+                .map(|register| registers.get(register));
+            hook.set(Some(signature == FIBONACCI));
+        }
+    });
+    let mut headless = Headless::new(soc);
+    match headless.run_until(FRAMES, |_| result.get().is_some()) {
+        Outcome::Met if result.get() == Some(true) => Report::Passed,
+        Outcome::Met => Report::Failed,
+        Outcome::Timeout => Report::Timeout,
+        Outcome::Error(_) => Report::Error,
+    }
+}
+
+/// A ROM loading `signature` in B/C/D/E/H/L then executing `LD B,B`
+fn signature_rom(name: &str, signature: [u8; 6]) -> PathBuf {
+    let loads = [0x06, 0x0E, 0x16, 0x1E, 0x26, 0x2E];
+    let mut code: Vec<u8> = loads
+        .into_iter()
+        .zip(signature)
+        .flat_map(<[u8; 2]>::from)
+        .collect();
+    code.extend([LD_B_B, 0x18, 0xFE]);
+    build_rom(name, &code)
+}
+
+#[test]
+fn test_harness_passed() {
+    let rom = signature_rom("gbmu_test_mooneye_passed.gb", FIBONACCI);
+
+    assert_eq!(run(&rom), Report::Passed);
+}
+
+#[test]
+fn test_harness_failed() {
+    let rom = signature_rom("gbmu_test_mooneye_failed.gb", [0x42; 6]);
+
+    assert_eq!(run(&rom), Report::Failed);
+}
+
+#[test]
+fn test_runs_on_dmg() {
+    let dmg = [
+        "add_sp_e_timing.gb",
+        "boot_div-dmgABCmgb.gb",
+        "boot_hwio-dmgABCmgb.gb",
+        "boot_regs-dmgABC.gb",
+        "di_timing-GS.gb",
+        "oam_dma/sources-GS.gb",
+        "ppu/intr_2_mode0_timing_sprites.gb",
+        "serial/boot_sclk_align-dmgABCmgb.gb",
+    ];
+    let others = [
+        "boot_div-S.gb",
+        "boot_div-dmg0.gb",
+        "boot_div2-S.gb",
+        "boot_hwio-S.gb",
+        "boot_hwio-dmg0.gb",
+        "boot_regs-dmg0.gb",
+        "boot_regs-mgb.gb",
+        "boot_regs-sgb.gb",
+        "boot_regs-sgb2.gb",
+    ];
+
+    assert!(dmg.iter().all(|rom| runs_on_dmg(Path::new(rom))));
+    assert!(!others.iter().any(|rom| runs_on_dmg(Path::new(rom))));
+}
+
+#[test]
+#[ignore = "needs the test ROMs"]
+fn test_mooneye_acceptance() {
+    let directory = test_roms("mooneye/acceptance");
+    let mut roms = Vec::new();
+    find(&directory, &mut roms);
+    roms.sort();
+
+    let reports: Vec<(String, Report)> = roms
+        .iter()
+        .map(|rom| {
+            let name = rom.strip_prefix(&directory).unwrap().display().to_string();
+            (name, run(rom))
+        })
+        .collect();
+    let width = reports
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    for (name, report) in &reports {
+        println!("{:width$} | {:?}", name, report, width = width);
+    }
+    let passed = reports
+        .iter()
+        .filter(|(_, report)| *report == Report::Passed)
+        .count();
+    println!("{} of {} passed", passed, reports.len());
+    assert_eq!(passed, reports.len());
+}
@@ -6,13 +6,14 @@ use crate::style::Theme;
 use button::Button;
 use iced::{Element, Length, Row, Space};
 use itertools::Itertools;
-use soc::{mode::Mode, System};
+use soc::{mode::Mode, System, SOC};
 
 pub struct Menu {
     right: Vec<Button>,
     left: Vec<Button>,
     breakpoints: VecDeque<u16>,
     status: System,
+    soc: SOC,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -24,10 +25,13 @@ pub enum MenuMsg {
     Second,
     Run,
     Breakpoint,
+    /// Step back one frame with the rewind buffer
+    Back,
 }
 
 impl Menu {
-    pub fn new(status: System) -> Self {
+    pub fn new(soc: SOC) -> Self {
+        let status = soc.borrow().get_status();
         let tick = Button::new(status.clone(), MenuMsg::Tick);
         let instruction = Button::new(status.clone(), MenuMsg::Instruction);
         let line = Button::new(status.clone(), MenuMsg::Line);
@@ -35,13 +39,15 @@ impl Menu {
         let second = Button::new(status.clone(), MenuMsg::Second);
         let run = Button::new(status.clone(), MenuMsg::Run);
         let breakpoint = Button::new(status.clone(), MenuMsg::Breakpoint);
-        let breakpoints = VecDeque::new();
+        let back = Button::new(status.clone(), MenuMsg::Back);
+        let breakpoints = soc.borrow().breakpoints().iter().copied().collect();
 
         Self {
-            left: vec![],
+            left: vec![back],
             right: vec![breakpoint, run, second, frame, line, instruction, tick],
             breakpoints,
             status,
+            soc,
         }
     }
 
@@ -73,6 +79,12 @@ impl Menu {
                     self.status.borrow_mut().mode(Mode::Breakpoint(breakpoint));
                 }
             }
+            MenuMsg::Back => {
+                self.status.borrow_mut().mode(Mode::Idle);
+                if !self.soc.borrow_mut().step_back() {
+                    println!("Nothing left to rewind");
+                }
+            }
             _ => {
                 if let Some(button) = self.left.iter().find(|&button| button.is_button(message)) {
                     button.update()
@@ -44,7 +44,7 @@ impl Button {
             MenuMsg::Run => {
                 status.run();
             }
-            MenuMsg::Breakpoint => {}
+            MenuMsg::Breakpoint | MenuMsg::Back => {}
         }
     }
 
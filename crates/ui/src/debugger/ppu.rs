@@ -71,12 +71,15 @@ impl View<PpuMsg> for Registers {
 
         let control = self.control.view(theme);
         let coordinates = self.coordinates.view(theme);
-        let bgp = self.bgp.view(theme);
+        let bgp = palette::render("BGP", &self.bgp, theme);
+        let objp0 = palette::render("OBP0", &self.objp0, theme);
+        let objp1 = palette::render("OBP1", &self.objp1, theme);
+        let palettes = Row::new().spacing(20).push(bgp).push(objp0).push(objp1);
         ppu.push(status)
             .push(control)
             .push(coordinates)
             .push(interupts)
-            .push(bgp)
+            .push(palettes)
             .into()
     }
 }
@@ -20,7 +20,6 @@ pub struct UserInterface {
 
 impl From<SOC> for UserInterface {
     fn from(soc: SOC) -> UserInterface {
-        let runner = soc.borrow().get_status();
         let ppu = soc.borrow().get_ppu();
         let cpu = soc.borrow().get_cpu();
         let memory = cpu.borrow().get_memory();
@@ -28,7 +27,7 @@ impl From<SOC> for UserInterface {
             theme: Theme::default(),
             cpu: Cpu::new(cpu.clone()),
             memory: Memory::new(memory),
-            menu: Menu::new(runner),
+            menu: Menu::new(soc.clone()),
             disassembler: Disassembler::new(cpu),
             ppu: Ppu::new(ppu),
         };
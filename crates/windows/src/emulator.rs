@@ -1,7 +1,9 @@
 use iced_wgpu::wgpu::util::StagingBelt;
 use soc::SOC;
+use std::time::{Duration, Instant};
 
-use gilrs::Gilrs;
+use gilrs::ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Repeat, Replay, Ticks};
+use gilrs::{EventType, Gilrs};
 use pixels::SurfaceTexture;
 use winit_input_helper::WinitInputHelper;
 
@@ -10,7 +12,7 @@ use iced_winit::{
     futures::{executor::LocalPool, task::SpawnExt},
     winit::{
         dpi::LogicalSize,
-        event::{ModifiersState, WindowEvent},
+        event::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent},
         event_loop::{ControlFlow, EventLoop},
         window::{Window, WindowBuilder, WindowId},
     },
@@ -19,6 +21,10 @@ use pixels::Pixels;
 
 const WIDTH: u32 = 160;
 const HEIGHT: u32 = 144;
+/// Holding this key plays the game backward
+const REWIND_KEY: VirtualKeyCode = VirtualKeyCode::Back;
+/// Frames are stepped back at the Game Boy refresh rate
+const REWIND_PERIOD: Duration = Duration::from_micros(16_742);
 
 pub struct Emulator {
     pub id: WindowId,
@@ -32,18 +38,22 @@ pub struct Emulator {
     pub soc: SOC,
     pub input: WinitInputHelper,
     pub gilrs: Gilrs,
+    pub rumble: Option<Effect>,
+    /// Set while the rewind key is held, with the time of the last step back
+    pub rewinding: Option<Instant>,
 }
 
 impl Emulator {
-    pub fn new(event_loop: &EventLoop<()>, soc: SOC) -> Self {
+    pub fn new(event_loop: &EventLoop<()>, soc: SOC, scale: u32) -> Self {
         let title = "GBMU";
         let input = WinitInputHelper::new();
         let gilrs = Gilrs::new().unwrap();
         let window = {
             let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
+            let scaled = LogicalSize::new((WIDTH * scale) as f64, (HEIGHT * scale) as f64);
             WindowBuilder::new()
                 .with_title(title)
-                .with_inner_size(size)
+                .with_inner_size(scaled)
                 .with_min_inner_size(size)
                 .build(event_loop)
                 .unwrap()
@@ -76,6 +86,42 @@ impl Emulator {
             soc,
             gilrs,
             input,
+            rumble: None,
+            rewinding: None,
+        }
+    }
+
+    /// Drive the force feedback of every connected gamepad from the cartridge
+    /// motor, the effect repeats until the motor is turned off
+    pub fn rumble(&mut self, on: bool) {
+        while let Some(event) = self.gilrs.next_event() {
+            if let EventType::Connected = event.event {
+                self.rumble = None;
+            }
+        }
+        if self.rumble.is_none() {
+            let gamepads: Vec<_> = self
+                .gilrs
+                .gamepads()
+                .filter(|(_, gamepad)| gamepad.is_ff_supported())
+                .map(|(id, _)| id)
+                .collect();
+            self.rumble = EffectBuilder::new()
+                .add_effect(BaseEffect {
+                    kind: BaseEffectType::Strong { magnitude: 0xC000 },
+                    scheduling: Replay {
+                        play_for: Ticks::from_ms(50),
+                        ..Default::default()
+                    },
+                    envelope: Default::default(),
+                })
+                .repeat(Repeat::Infinitely)
+                .gamepads(&gamepads)
+                .finish(&mut self.gilrs)
+                .ok();
+        }
+        if let Some(effect) = &self.rumble {
+            let _ = if on { effect.play() } else { effect.stop() };
         }
     }
 
@@ -92,6 +138,29 @@ impl Emulator {
             WindowEvent::ModifiersChanged(new_modifiers) => {
                 self.modifiers = new_modifiers;
             }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(REWIND_KEY),
+                        state,
+                        ..
+                    },
+                ..
+            } => {
+                self.rewinding = match state {
+                    ElementState::Pressed => {
+                        self.rewinding.or(Some(Instant::now() - REWIND_PERIOD))
+                    }
+                    ElementState::Released => None,
+                };
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                // The cursor distance to the center of the screen tilts the console
+                let size = self.window.inner_size();
+                let x = (position.x / size.width as f64) * 2.0 - 1.0;
+                let y = (position.y / size.height as f64) * 2.0 - 1.0;
+                self.soc.borrow().set_tilt(x as f32, y as f32);
+            }
             _ => (),
         };
         if let Some(event) = window_event(&event, self.window.scale_factor(), self.modifiers) {
@@ -99,6 +168,20 @@ impl Emulator {
         }
     }
 
+    /// While the rewind key is held the machine steps back one frame per
+    /// period instead of running, returns true when it did
+    pub fn rewind(&mut self) -> bool {
+        match self.rewinding {
+            Some(last) if last.elapsed() >= REWIND_PERIOD => {
+                self.rewinding = Some(Instant::now());
+                self.soc.borrow_mut().step_back();
+                true
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
     pub fn update(&mut self) {
         self.state.update();
     }
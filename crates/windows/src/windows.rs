@@ -2,7 +2,7 @@ use iced_wgpu::wgpu::Instance;
 use iced_winit::winit::event::{Event, StartCause};
 use iced_winit::winit::event_loop::EventLoop;
 use shared::Redraw;
-use soc::{TryInit, SOC};
+use soc::{Event as SocEvent, SOC};
 
 use crate::debugger;
 use crate::emulator;
@@ -10,8 +10,8 @@ use crate::emulator;
 pub struct Windows {}
 
 impl Windows {
-    pub fn run(name: &str) {
-        let soc = SOC::try_init(name).unwrap();
+    /// Open the debugger and the emulator window, `scale` times the Game Boy screen
+    pub fn run(soc: SOC, scale: u32) {
         let event_loop = EventLoop::new();
 
         // Fix draw on top of fullscreen issue on macos
@@ -24,7 +24,7 @@ impl Windows {
 
         let instance = Instance::new(iced_wgpu::wgpu::Backends::PRIMARY);
         let mut debugger = debugger::Debugger::new(&event_loop, &instance, soc.clone());
-        let mut emulator = emulator::Emulator::new(&event_loop, soc.clone());
+        let mut emulator = emulator::Emulator::new(&event_loop, soc.clone(), scale);
         event_loop.run(move |event, _, flow| {
             // Handle Events
             match event {
@@ -36,8 +36,13 @@ impl Windows {
                     emulator.process_event(event, flow);
                 }
                 Event::MainEventsCleared => {
-                    // Run Emulator here
-                    match soc.borrow_mut().run() {
+                    // Run Emulator here, unless it is played backward
+                    let redraw = if emulator.rewind() {
+                        Redraw::Nope
+                    } else {
+                        soc.borrow_mut().run()
+                    };
+                    match redraw {
                         Redraw::Emulator => {
                             emulator.request_redraw();
                         }
@@ -48,6 +53,17 @@ impl Windows {
                         }
                         Redraw::Nope => (),
                     }
+                    for event in soc.borrow_mut().take_events() {
+                        match event {
+                            SocEvent::Rumble(on) => emulator.rumble(on),
+                            SocEvent::StateSaved(slot) => println!("State saved in slot {}", slot),
+                            SocEvent::Rewound => {
+                                debugger.state.refresh();
+                                emulator.request_redraw();
+                            }
+                            SocEvent::Error(error) => eprintln!("Machine error: {}", error),
+                        }
+                    }
                     if !debugger.state.state.is_queue_empty() {
                         debugger.request_redraw();
                     }